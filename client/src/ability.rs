@@ -0,0 +1,410 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    combat::AimTarget,
+    controller::MoveCharacters,
+    data::load_ron,
+    gate_lock::{GateLock, LockSpeedBoost},
+    health::{Dead, Shield},
+    input_map::{Action, Actions},
+    player::LocalPlayer,
+    robot::{Robot, Team},
+    shake::ScreenShake,
+    status::{ApplyStatusEffectEvent, StatusEffectKind, StatusEffects},
+};
+
+// Area abilities go off with a bang, shaking the screen out to a multiple of their radius
+const AREA_TRAUMA: f32 = 0.5;
+const AREA_TRAUMA_RANGE: f32 = 2.5;
+
+pub struct AbilityPlugin;
+
+impl Plugin for AbilityPlugin {
+    fn build(&self, app: &mut App) {
+        let file: AbilityFile =
+            load_ron("abilities.ron").unwrap_or_else(|err| panic!("bad ability file {}", err));
+
+        app.insert_resource(AbilityLibrary {
+            abilities: file.abilities,
+            loadout: file.loadout,
+        })
+        .add_event::<CastAbilityEvent>()
+        .add_system(equip_robots)
+        .add_system(regenerate_energy)
+        .add_system(tick_cooldowns)
+        .add_system(ability_input)
+        .add_system(start_casts)
+        .add_system(finish_casts)
+        .add_system(dash.after(MoveCharacters));
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AbilityDefinition {
+    pub name: String,
+    pub cooldown: f32,
+    pub cast_time: f32,
+    pub cost: f32,
+    pub targeting: Targeting,
+    pub effects: Vec<AbilityEffect>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Targeting {
+    // Affects the caster only
+    SelfCast,
+    // Aimed at a point on the floor, usually the cursor
+    Direction,
+    // The closest gate lock to the caster, if one is in range
+    NearestLock { range: f32 },
+}
+
+#[derive(Deserialize, Clone)]
+pub enum AbilityEffect {
+    Dash {
+        distance: f32,
+        duration: f32,
+    },
+    Shield {
+        duration: f32,
+        damage_reduction: f32,
+    },
+    LockSpeedBoost {
+        duration: f32,
+        multiplier: f32,
+    },
+    JamLock {
+        duration: f32,
+    },
+    // Applies a status effect to every enemy robot within `radius` of the caster
+    AreaStatus {
+        kind: StatusEffectKind,
+        duration: f32,
+        magnitude: f32,
+        radius: f32,
+    },
+}
+
+#[derive(Deserialize)]
+struct AbilityFile {
+    abilities: Vec<AbilityDefinition>,
+    loadout: Vec<String>,
+}
+
+pub struct AbilityLibrary {
+    pub abilities: Vec<AbilityDefinition>,
+    loadout: Vec<String>,
+}
+
+impl AbilityLibrary {
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.abilities
+            .iter()
+            .position(|ability| ability.name == name)
+    }
+}
+
+#[derive(Component)]
+pub struct Energy {
+    pub current: f32,
+    pub max: f32,
+    pub regen_per_second: f32,
+}
+
+pub struct AbilitySlot {
+    // Index into AbilityLibrary::abilities
+    pub ability: usize,
+    pub cooldown_remaining: f32,
+}
+
+#[derive(Component)]
+pub struct Abilities {
+    pub slots: Vec<AbilitySlot>,
+}
+
+#[derive(Clone, Copy)]
+enum AbilityTarget {
+    Caster,
+    Point(Vec3),
+    Lock(Entity),
+}
+
+// Present while a robot is winding up an ability with a cast time
+#[derive(Component)]
+pub struct Casting {
+    ability: usize,
+    target: AbilityTarget,
+    remaining: f32,
+}
+
+#[derive(Component)]
+struct Dashing {
+    velocity: Vec3,
+    remaining: f32,
+}
+
+// Ask for `caster` to use the ability in `slot`. Whether it actually goes off depends on
+// cooldowns, energy and targeting, so anything (player input, bots) can send these freely.
+pub struct CastAbilityEvent {
+    pub caster: Entity,
+    pub slot: usize,
+    pub aim: Option<Vec3>,
+}
+
+fn equip_robots(
+    mut commands: Commands,
+    library: Res<AbilityLibrary>,
+    robots: Query<Entity, (With<Robot>, Without<Abilities>)>,
+) {
+    for entity in robots.iter() {
+        let slots = library
+            .loadout
+            .iter()
+            .filter_map(|name| match library.find(name) {
+                Some(ability) => Some(AbilitySlot {
+                    ability,
+                    cooldown_remaining: 0.0,
+                }),
+                None => {
+                    println!("Loadout references unknown ability {}", name);
+                    None
+                }
+            })
+            .collect();
+
+        commands
+            .entity(entity)
+            .insert(Abilities { slots })
+            .insert(Energy {
+                current: 100.0,
+                max: 100.0,
+                regen_per_second: 8.0,
+            });
+    }
+}
+
+fn regenerate_energy(time: Res<Time>, mut energies: Query<&mut Energy, Without<Dead>>) {
+    for mut energy in energies.iter_mut() {
+        energy.current =
+            (energy.current + energy.regen_per_second * time.delta_seconds()).min(energy.max);
+    }
+}
+
+fn tick_cooldowns(time: Res<Time>, mut abilities: Query<&mut Abilities>) {
+    for mut abilities in abilities.iter_mut() {
+        for slot in abilities.slots.iter_mut() {
+            slot.cooldown_remaining = (slot.cooldown_remaining - time.delta_seconds()).max(0.0);
+        }
+    }
+}
+
+fn ability_input(
+    actions: Res<Actions>,
+    aim_target: Res<AimTarget>,
+    player: Query<(Entity, Option<&StatusEffects>), (With<LocalPlayer>, Without<Dead>)>,
+    mut cast_events: EventWriter<CastAbilityEvent>,
+) {
+    for (caster, status_effects) in player.iter() {
+        if status_effects.map_or(false, |effects| effects.blocks_input()) {
+            continue;
+        }
+
+        for (slot, action) in Action::ABILITIES.iter().enumerate() {
+            if actions.just_pressed(*action) {
+                cast_events.send(CastAbilityEvent {
+                    caster,
+                    slot,
+                    aim: aim_target.0,
+                });
+            }
+        }
+    }
+}
+
+fn start_casts(
+    mut commands: Commands,
+    library: Res<AbilityLibrary>,
+    mut casters: Query<
+        (&Transform, &mut Abilities, &mut Energy),
+        (Without<Dead>, Without<Casting>),
+    >,
+    locks: Query<(Entity, &GlobalTransform), With<GateLock>>,
+    mut cast_events: EventReader<CastAbilityEvent>,
+) {
+    for event in cast_events.iter() {
+        let (transform, mut abilities, mut energy) = match casters.get_mut(event.caster) {
+            Ok(caster) => caster,
+            Err(_) => continue,
+        };
+
+        let slot = match abilities.slots.get_mut(event.slot) {
+            Some(slot) => slot,
+            None => continue,
+        };
+        let definition = &library.abilities[slot.ability];
+
+        if slot.cooldown_remaining > 0.0 || energy.current < definition.cost {
+            continue;
+        }
+
+        let target = match definition.targeting {
+            Targeting::SelfCast => AbilityTarget::Caster,
+            Targeting::Direction => match event.aim {
+                Some(aim) => AbilityTarget::Point(aim),
+                None => continue,
+            },
+            Targeting::NearestLock { range } => {
+                let nearest = locks
+                    .iter()
+                    .map(|(lock, lock_transform)| {
+                        let distance = lock_transform.translation().distance(transform.translation);
+                        (lock, distance)
+                    })
+                    .filter(|(_, distance)| *distance <= range)
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+                match nearest {
+                    Some((lock, _)) => AbilityTarget::Lock(lock),
+                    None => continue,
+                }
+            }
+        };
+
+        energy.current -= definition.cost;
+        slot.cooldown_remaining = definition.cooldown;
+        println!("{:?} casting {}", event.caster, definition.name);
+
+        // the cast resolves in finish_casts, even with no cast time, so every ability goes
+        // through the same path
+        commands.entity(event.caster).insert(Casting {
+            ability: slot.ability,
+            target,
+            remaining: definition.cast_time,
+        });
+    }
+}
+
+fn finish_casts(
+    mut commands: Commands,
+    time: Res<Time>,
+    library: Res<AbilityLibrary>,
+    mut casters: Query<(Entity, &Transform, &Team, &mut Casting, Option<&Dead>)>,
+    robots: Query<(Entity, &Transform, &Team), With<Robot>>,
+    mut locks: Query<&mut GateLock>,
+    mut screen_shake: ResMut<ScreenShake>,
+    mut status_events: EventWriter<ApplyStatusEffectEvent>,
+) {
+    for (caster, transform, team, mut casting, dead) in casters.iter_mut() {
+        // dying interrupts the cast, the cost and cooldown are already spent
+        if dead.is_some() {
+            commands.entity(caster).remove::<Casting>();
+            continue;
+        }
+
+        casting.remaining -= time.delta_seconds();
+        if casting.remaining > 0.0 {
+            continue;
+        }
+        commands.entity(caster).remove::<Casting>();
+
+        for effect in library.abilities[casting.ability].effects.iter() {
+            match (effect, casting.target) {
+                (AbilityEffect::Dash { distance, duration }, target) => {
+                    let mut direction = match target {
+                        AbilityTarget::Point(point) => point - transform.translation,
+                        _ => transform.rotation * Vec3::Z,
+                    };
+                    direction.y = 0.0;
+
+                    commands.entity(caster).insert(Dashing {
+                        velocity: direction.normalize_or_zero() * *distance / *duration,
+                        remaining: *duration,
+                    });
+                }
+                (
+                    AbilityEffect::Shield {
+                        duration,
+                        damage_reduction,
+                    },
+                    _,
+                ) => {
+                    commands.entity(caster).insert(Shield {
+                        damage_reduction: *damage_reduction,
+                        remaining: *duration,
+                    });
+                }
+                (
+                    AbilityEffect::LockSpeedBoost {
+                        duration,
+                        multiplier,
+                    },
+                    _,
+                ) => {
+                    commands.entity(caster).insert(LockSpeedBoost {
+                        multiplier: *multiplier,
+                        remaining: *duration,
+                    });
+                }
+                (AbilityEffect::JamLock { duration }, AbilityTarget::Lock(lock)) => {
+                    if let Ok(mut lock) = locks.get_mut(lock) {
+                        lock.jam(*duration);
+                    }
+                }
+                (AbilityEffect::JamLock { .. }, _) => {}
+                (
+                    AbilityEffect::AreaStatus {
+                        kind,
+                        duration,
+                        magnitude,
+                        radius,
+                    },
+                    _,
+                ) => {
+                    screen_shake.add_trauma_at(
+                        transform.translation,
+                        AREA_TRAUMA,
+                        *radius * AREA_TRAUMA_RANGE,
+                    );
+
+                    for (target, target_transform, target_team) in robots.iter() {
+                        if target_team == team
+                            || target_transform.translation.distance(transform.translation)
+                                > *radius
+                        {
+                            continue;
+                        }
+
+                        status_events.send(ApplyStatusEffectEvent {
+                            target,
+                            kind: *kind,
+                            duration: *duration,
+                            magnitude: *magnitude,
+                            origin: Some(transform.translation),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn dash(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dashers: Query<(Entity, &mut Velocity, &mut Dashing, Option<&StatusEffects>)>,
+) {
+    for (entity, mut velocity, mut dashing, status_effects) in dashers.iter_mut() {
+        // a root or stun landing mid-dash stops it dead
+        if status_effects.map_or(true, |effects| effects.speed_multiplier() > 0.0) {
+            velocity.linvel.x = dashing.velocity.x;
+            velocity.linvel.z = dashing.velocity.z;
+        }
+
+        dashing.remaining -= time.delta_seconds();
+        if dashing.remaining <= 0.0 {
+            commands.entity(entity).remove::<Dashing>();
+        }
+    }
+}
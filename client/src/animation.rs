@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use bevy::{animation::animation_player, prelude::*, transform::TransformSystem};
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    controller::MoveCharacters,
+    ctf::FlagCapturedEvent,
+    data::load_ron,
+    health::{DamageEvent, Dead},
+    robot::{Robot, RobotRig, Team},
+};
+
+// Slowest and fastest a clip is played to keep its feet on the ground
+const MIN_PLAYBACK_SPEED: f32 = 0.5;
+const MAX_PLAYBACK_SPEED: f32 = 2.0;
+
+pub struct RobotAnimationPlugin;
+
+impl Plugin for RobotAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        let file: AnimationFile =
+            load_ron("animations.ron").unwrap_or_else(|err| panic!("bad animation file {}", err));
+        for animation in RobotAnimation::ALL {
+            if !file.states.contains_key(&animation) {
+                panic!("animation state {:?} is not defined", animation);
+            }
+        }
+
+        let asset_server = app.world.resource::<AssetServer>();
+        let states = file
+            .states
+            .into_iter()
+            .map(|(animation, state)| {
+                let handle = asset_server.load(state.clip.as_str());
+                (animation, AnimationNode { handle, ..state })
+            })
+            .collect();
+
+        app.insert_resource(AnimationGraph {
+            walk_speed: file.walk_speed,
+            run_speed: file.run_speed,
+            crossfade: file.crossfade,
+            states,
+        })
+        .add_event::<PlayAnimationEvent>()
+        .add_system(add_animators)
+        .add_system(trigger_animations.label(TriggerAnimations))
+        .add_system(
+            update_animators
+                .after(TriggerAnimations)
+                .after(MoveCharacters),
+        )
+        // blends over the pose the animation player has just written, before it is used
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            blend_crossfades
+                .after(animation_player)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct TriggerAnimations;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RobotAnimation {
+    Idle,
+    Walk,
+    Run,
+    Attack,
+    Hit,
+    Death,
+    Celebrate,
+}
+
+impl RobotAnimation {
+    const ALL: [RobotAnimation; 7] = [
+        RobotAnimation::Idle,
+        RobotAnimation::Walk,
+        RobotAnimation::Run,
+        RobotAnimation::Attack,
+        RobotAnimation::Hit,
+        RobotAnimation::Death,
+        RobotAnimation::Celebrate,
+    ];
+}
+
+#[derive(Deserialize, Clone)]
+struct AnimationNode {
+    // Asset path of the clip, like "Robot.glb#Animation2"
+    clip: String,
+    #[serde(skip)]
+    handle: Handle<AnimationClip>,
+    #[serde(default)]
+    repeat: bool,
+    // Seconds a one-shot plays for before the robot goes back to moving about
+    #[serde(default)]
+    duration: Option<f32>,
+    #[serde(default)]
+    priority: u32,
+    // Units per second across the floor the clip's feet move at when played at normal speed.
+    // Clips with one are sped up or slowed down to match how fast the robot is actually going.
+    #[serde(default)]
+    stride_speed: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct AnimationFile {
+    walk_speed: f32,
+    run_speed: f32,
+    // Seconds spent blending from one animation into the next
+    crossfade: f32,
+    states: HashMap<RobotAnimation, AnimationNode>,
+}
+
+struct AnimationGraph {
+    walk_speed: f32,
+    run_speed: f32,
+    // Seconds spent blending from one animation into the next
+    crossfade: f32,
+    states: HashMap<RobotAnimation, AnimationNode>,
+}
+
+impl AnimationGraph {
+    fn node(&self, animation: RobotAnimation) -> &AnimationNode {
+        &self.states[&animation]
+    }
+
+    // Idle, walk or run, by how fast a robot is moving across the floor
+    fn locomotion(&self, velocity: Vec3) -> RobotAnimation {
+        let speed = ground_speed(velocity);
+        if speed >= self.run_speed {
+            RobotAnimation::Run
+        } else if speed >= self.walk_speed {
+            RobotAnimation::Walk
+        } else {
+            RobotAnimation::Idle
+        }
+    }
+}
+
+fn ground_speed(velocity: Vec3) -> f32 {
+    Vec3::new(velocity.x, 0.0, velocity.z).length()
+}
+
+// Send this to play a one-shot, like an attack, on a robot. It's dropped if the robot is dead or
+// busy with a one-shot of higher priority.
+pub struct PlayAnimationEvent {
+    pub robot: Entity,
+    pub animation: RobotAnimation,
+}
+
+// Which animation a robot is showing. Every robot gets one.
+#[derive(Component, Default)]
+pub struct Animator {
+    // What its animation player was last told to play, None until its model has loaded
+    playing: Option<RobotAnimation>,
+    // The one-shot in progress and the seconds it has left
+    one_shot: Option<(RobotAnimation, f32)>,
+    // Set when a one-shot starts, so the same one asked for again plays from the top
+    restart: bool,
+    crossfade: Option<Crossfade>,
+}
+
+// The pose a robot was in when its animation changed, faded out as the new one takes over.
+// Blending from a still pose rather than the old clip means a change part way through a fade
+// picks up from wherever the robot is, without popping.
+struct Crossfade {
+    // Every bone's local transform
+    from: Vec<(Entity, Transform)>,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl Animator {
+    fn start_one_shot(&mut self, graph: &AnimationGraph, animation: RobotAnimation) {
+        let node = graph.node(animation);
+        let duration = match node.duration {
+            Some(duration) => duration,
+            None => return,
+        };
+        let busy = self.one_shot.map_or(false, |(current, _)| {
+            graph.node(current).priority > node.priority
+        });
+        if !busy {
+            self.one_shot = Some((animation, duration));
+            self.restart = true;
+        }
+    }
+}
+
+fn add_animators(mut commands: Commands, robots: Query<Entity, (With<Robot>, Without<Animator>)>) {
+    for entity in robots.iter() {
+        commands.entity(entity).insert(Animator::default());
+    }
+}
+
+// Turns what happens in the match into one-shots
+fn trigger_animations(
+    graph: Res<AnimationGraph>,
+    mut animators: Query<(&mut Animator, &Team), Without<Dead>>,
+    mut play_events: EventReader<PlayAnimationEvent>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut captured_events: EventReader<FlagCapturedEvent>,
+) {
+    let requests = play_events
+        .iter()
+        .map(|event| (event.robot, event.animation))
+        .chain(
+            damage_events
+                .iter()
+                .map(|event| (event.target, RobotAnimation::Hit)),
+        );
+    for (robot, animation) in requests {
+        if let Ok((mut animator, _)) = animators.get_mut(robot) {
+            animator.start_one_shot(&graph, animation);
+        }
+    }
+
+    // the whole team celebrates a capture
+    for event in captured_events.iter() {
+        for (mut animator, team) in animators.iter_mut() {
+            if *team == event.team {
+                animator.start_one_shot(&graph, RobotAnimation::Celebrate);
+            }
+        }
+    }
+}
+
+// Picks each robot's animation from its state, fading into it whenever that changes, and keeps
+// walking and running in step with how fast the robot is going
+fn update_animators(
+    time: Res<Time>,
+    graph: Res<AnimationGraph>,
+    mut robots: Query<(
+        &mut Animator,
+        Option<&Velocity>,
+        Option<&Dead>,
+        Option<&RobotRig>,
+    )>,
+    mut animation_players: Query<&mut AnimationPlayer>,
+    bones: Query<(&Transform, Option<&Children>)>,
+) {
+    for (mut animator, velocity, dead, rig) in robots.iter_mut() {
+        let velocity = velocity.map_or(Vec3::ZERO, |velocity| velocity.linvel);
+        if let Some((_, remaining)) = animator.one_shot.as_mut() {
+            *remaining -= time.delta_seconds();
+        }
+        if dead.is_some()
+            || animator
+                .one_shot
+                .map_or(false, |(_, remaining)| remaining <= 0.0)
+        {
+            animator.one_shot = None;
+        }
+
+        let animation = match (dead, animator.one_shot) {
+            (Some(_), _) => RobotAnimation::Death,
+            (None, Some((one_shot, _))) => one_shot,
+            (None, None) => graph.locomotion(velocity),
+        };
+
+        // nothing to play until the robot's model has loaded
+        let animation_entity = match rig.and_then(|rig| rig.animation_player) {
+            Some(animation_entity) => animation_entity,
+            None => continue,
+        };
+        let mut animation_player = match animation_players.get_mut(animation_entity) {
+            Ok(animation_player) => animation_player,
+            Err(_) => continue,
+        };
+
+        let node = graph.node(animation);
+        if animator.playing != Some(animation) || animator.restart {
+            // the first animation a robot plays has nothing to fade from
+            if animator.playing.is_some() && graph.crossfade > 0.0 {
+                let mut from = Vec::new();
+                collect_pose(animation_entity, &bones, &mut from);
+                animator.crossfade = Some(Crossfade {
+                    from,
+                    elapsed: 0.0,
+                    duration: graph.crossfade,
+                });
+            }
+
+            animation_player.play(node.handle.clone());
+            if node.repeat {
+                animation_player.repeat();
+            }
+            animator.playing = Some(animation);
+            animator.restart = false;
+        }
+
+        if let Some(stride_speed) = node.stride_speed {
+            let speed = ground_speed(velocity) / stride_speed;
+            animation_player.set_speed(speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED));
+        }
+    }
+}
+
+// The local transform of `entity` and everything below it
+fn collect_pose(
+    entity: Entity,
+    bones: &Query<(&Transform, Option<&Children>)>,
+    pose: &mut Vec<(Entity, Transform)>,
+) {
+    let (transform, children) = match bones.get(entity) {
+        Ok(bone) => bone,
+        Err(_) => return,
+    };
+    pose.push((entity, *transform));
+    for child in children.into_iter().flatten() {
+        collect_pose(*child, bones, pose);
+    }
+}
+
+fn blend_crossfades(
+    time: Res<Time>,
+    mut animators: Query<&mut Animator>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for mut animator in animators.iter_mut() {
+        let crossfade = match animator.crossfade.as_mut() {
+            Some(crossfade) => crossfade,
+            None => continue,
+        };
+
+        crossfade.elapsed += time.delta_seconds();
+        let t = (crossfade.elapsed / crossfade.duration).min(1.0);
+        // eases in and out, so the blend doesn't start or stop with a jolt
+        let t = t * t * (3.0 - 2.0 * t);
+
+        for (entity, from) in crossfade.from.iter() {
+            if let Ok(mut transform) = transforms.get_mut(*entity) {
+                let to = *transform;
+                *transform = Transform {
+                    translation: from.translation.lerp(to.translation, t),
+                    rotation: from.rotation.slerp(to.rotation, t),
+                    scale: from.scale.lerp(to.scale, t),
+                };
+            }
+        }
+
+        if t >= 1.0 {
+            animator.crossfade = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> AnimationGraph {
+        let file: AnimationFile =
+            ron::from_str(include_str!("../assets/animations.ron")).expect("bad animation file");
+        AnimationGraph {
+            walk_speed: file.walk_speed,
+            run_speed: file.run_speed,
+            crossfade: file.crossfade,
+            states: file.states,
+        }
+    }
+
+    #[test]
+    fn locomotion_follows_speed() {
+        let graph = graph();
+        assert_eq!(graph.locomotion(Vec3::ZERO), RobotAnimation::Idle);
+        assert_eq!(
+            graph.locomotion(Vec3::new(0.0, -20.0, 0.0)),
+            RobotAnimation::Idle
+        );
+        assert_eq!(
+            graph.locomotion(Vec3::new(3.0, 0.0, 4.0)),
+            RobotAnimation::Walk
+        );
+        assert_eq!(
+            graph.locomotion(Vec3::new(15.0, 0.0, 0.0)),
+            RobotAnimation::Run
+        );
+    }
+
+    #[test]
+    fn one_shots_respect_priority() {
+        let graph = graph();
+        let mut animator = Animator::default();
+
+        animator.start_one_shot(&graph, RobotAnimation::Attack);
+        animator.start_one_shot(&graph, RobotAnimation::Hit);
+        assert_eq!(
+            animator.one_shot.map(|(a, _)| a),
+            Some(RobotAnimation::Attack)
+        );
+
+        // looping states can't be started as one-shots
+        animator.one_shot = None;
+        animator.start_one_shot(&graph, RobotAnimation::Walk);
+        assert!(animator.one_shot.is_none());
+    }
+}
@@ -0,0 +1,778 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    combat::{AttackEvent, AttackKind, MELEE_RANGE},
+    ctf::FlagCarrier,
+    data::load_ron,
+    difficulty::{BotDifficulty, BotRng},
+    gate::{Gate, GateSide, GateState},
+    gate_lock::GateLock,
+    health::{Dead, Health},
+    map::MapOptions,
+    npc::{NpcBrain, NpcObjective, NpcPlayer, ARRIVE_RADIUS},
+    perception::{KnownEnemy, Perceive, Perception},
+    robot::{Robot, Team},
+};
+
+// How close an enemy has to be to a lock for going to it to count as contesting it
+const CONTEST_RADIUS: f32 = 8.0;
+
+pub struct BehaviourPlugin;
+
+impl Plugin for BehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        let file: BehaviourFile =
+            load_ron("behaviours.ron").unwrap_or_else(|err| panic!("bad behaviour file {}", err));
+        if !file.trees.contains_key(&file.default_tree) {
+            panic!(
+                "default behaviour tree {} is not defined",
+                file.default_tree
+            );
+        }
+
+        app.insert_resource(BehaviourLibrary {
+            trees: file.trees,
+            default_tree: file.default_tree,
+        })
+        .add_system(attach_behaviours)
+        .add_system(run_behaviours.label(RunBehaviours).after(Perceive));
+    }
+}
+
+// Systems acting on what the trees decided this frame run after this
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RunBehaviours;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BehaviourStatus {
+    Success,
+    Failure,
+    Running,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum BehaviourNode {
+    // Ticks children in order until one succeeds or is still running
+    Selector(Vec<BehaviourNode>),
+    // Ticks children in order until one fails or is still running
+    Sequence(Vec<BehaviourNode>),
+    // Swaps success and failure
+    Invert(Box<BehaviourNode>),
+    // Succeeds whatever the child did, unless it is still running
+    AlwaysSucceed(Box<BehaviourNode>),
+    // Fails without ticking the child until `seconds` after it last succeeded. The timer lives
+    // on the blackboard under `timer`, so nodes sharing a name share a cooldown.
+    Cooldown {
+        timer: String,
+        seconds: f32,
+        child: Box<BehaviourNode>,
+    },
+    Condition(Condition),
+    Action(Action),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Condition {
+    CarryingFlag,
+    EnemyGateOpen,
+    // Health as a fraction of the maximum
+    HealthBelow(f32),
+    // Remembers the closest enemy in sight and in range on the blackboard as "enemy"
+    EnemyWithin(f32),
+    // Whether "enemy" on the blackboard is in sight and close enough to hit with a melee swing
+    EnemyInMeleeRange,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Action {
+    // Walks to the destination, succeeding on arrival
+    MoveTo(Destination),
+    // Walks to a free lock on the enemy gate and stands on it until it unlocks. Fails when there
+    // are none left to take.
+    CaptureLock,
+    // Attacks "enemy" from the blackboard if it's in sight, melee if close enough and ranged
+    // otherwise
+    Attack,
+    // Runs directly away from where "enemy" on the blackboard was last seen
+    Flee { distance: f32 },
+    // Walks to where "enemy" was last seen, or else to the latest noise heard, and forgets
+    // about it on arrival. Fails when there is nothing to look into.
+    Investigate,
+    // Stands still
+    Idle,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Destination {
+    // Just inside the enemy gate, on our side of it
+    EnemyGate,
+    EnemyGoalRoom,
+    HomeGate,
+    HomeGoalRoom,
+}
+
+#[derive(Deserialize)]
+struct BehaviourFile {
+    trees: HashMap<String, BehaviourNode>,
+    default_tree: String,
+}
+
+pub struct BehaviourLibrary {
+    pub trees: HashMap<String, BehaviourNode>,
+    pub default_tree: String,
+}
+
+// Which tree a bot runs, by name in the BehaviourLibrary
+#[derive(Component)]
+pub struct Behaviour {
+    pub tree: String,
+}
+
+// Memory shared between the nodes of one bot's tree
+#[derive(Component, Default)]
+pub struct Blackboard {
+    pub entities: HashMap<String, Entity>,
+    // Seconds left on each named timer, counted down every frame
+    pub timers: HashMap<String, f32>,
+}
+
+struct LockInfo {
+    entity: Entity,
+    side: GateSide,
+    position: Vec3,
+    unlocked: bool,
+    held_by: Option<Entity>,
+    held_by_team: Option<Team>,
+}
+
+// The parts of the world every bot knows about, gathered once per frame. Other robots are only
+// known through each bot's own Perception.
+struct WorldView<'a> {
+    map_options: &'a MapOptions,
+    locks: Vec<LockInfo>,
+    open_gates: Vec<GateSide>,
+    // Locks bots are already going for, so teammates split up instead of crowding one
+    claimed_locks: Vec<(Entity, Entity)>,
+}
+
+// The bot being ticked and where its decisions go
+struct Agent<'a, 'w, 's> {
+    entity: Entity,
+    team: Team,
+    position: Vec3,
+    health: f32,
+    carrying_flag: bool,
+    brain: &'a mut NpcBrain,
+    blackboard: &'a mut Blackboard,
+    perception: &'a mut Perception,
+    difficulty: &'a BotDifficulty,
+    rng: &'a mut BotRng,
+    attack_events: &'a mut EventWriter<'w, 's, AttackEvent>,
+}
+
+impl<'a, 'w, 's> Agent<'a, 'w, 's> {
+    // What we remember of "enemy" on the blackboard, if anything
+    fn enemy(&self) -> Option<KnownEnemy> {
+        let enemy = *self.blackboard.entities.get("enemy")?;
+        self.perception.known_enemy(enemy).copied()
+    }
+
+    // Sets where the bot walks and reports whether it is already there
+    fn walk_to(&mut self, destination: Vec3) -> BehaviourStatus {
+        let destination = Vec3::new(destination.x, 0.0, destination.z);
+        self.brain.destination = Some(destination);
+
+        let mut offset = destination - self.position;
+        offset.y = 0.0;
+        if offset.length() < ARRIVE_RADIUS {
+            BehaviourStatus::Success
+        } else {
+            BehaviourStatus::Running
+        }
+    }
+}
+
+impl BehaviourNode {
+    fn tick(&self, agent: &mut Agent, world: &WorldView) -> BehaviourStatus {
+        match self {
+            BehaviourNode::Selector(children) => {
+                for child in children {
+                    match child.tick(agent, world) {
+                        BehaviourStatus::Failure => continue,
+                        status => return status,
+                    }
+                }
+                BehaviourStatus::Failure
+            }
+            BehaviourNode::Sequence(children) => {
+                for child in children {
+                    match child.tick(agent, world) {
+                        BehaviourStatus::Success => continue,
+                        status => return status,
+                    }
+                }
+                BehaviourStatus::Success
+            }
+            BehaviourNode::Invert(child) => match child.tick(agent, world) {
+                BehaviourStatus::Success => BehaviourStatus::Failure,
+                BehaviourStatus::Failure => BehaviourStatus::Success,
+                BehaviourStatus::Running => BehaviourStatus::Running,
+            },
+            BehaviourNode::AlwaysSucceed(child) => match child.tick(agent, world) {
+                BehaviourStatus::Running => BehaviourStatus::Running,
+                _ => BehaviourStatus::Success,
+            },
+            BehaviourNode::Cooldown {
+                timer,
+                seconds,
+                child,
+            } => {
+                if agent
+                    .blackboard
+                    .timers
+                    .get(timer)
+                    .map_or(false, |t| *t > 0.0)
+                {
+                    return BehaviourStatus::Failure;
+                }
+
+                let status = child.tick(agent, world);
+                if status == BehaviourStatus::Success {
+                    agent.blackboard.timers.insert(timer.clone(), *seconds);
+                }
+                status
+            }
+            BehaviourNode::Condition(condition) => {
+                if condition.check(agent, world) {
+                    BehaviourStatus::Success
+                } else {
+                    BehaviourStatus::Failure
+                }
+            }
+            BehaviourNode::Action(action) => action.run(agent, world),
+        }
+    }
+}
+
+impl Condition {
+    fn check(&self, agent: &mut Agent, world: &WorldView) -> bool {
+        match self {
+            Condition::CarryingFlag => agent.carrying_flag,
+            Condition::EnemyGateOpen => world.open_gates.contains(&agent.team.enemy().home_side()),
+            Condition::HealthBelow(fraction) => agent.health < *fraction,
+            Condition::EnemyWithin(range) => {
+                let nearest = agent
+                    .perception
+                    .noticed_enemies()
+                    .filter(|known| known.visible())
+                    .map(|known| {
+                        let distance = known.last_seen_position.distance(agent.position);
+                        (known.entity, distance)
+                    })
+                    .filter(|(_, distance)| distance <= range)
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+                // a miss keeps the old "enemy" so it can still be investigated or fled from
+                match nearest {
+                    Some((enemy, _)) => {
+                        agent.blackboard.entities.insert("enemy".to_string(), enemy);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Condition::EnemyInMeleeRange => agent.enemy().map_or(false, |enemy| {
+                enemy.visible() && enemy.last_seen_position.distance(agent.position) <= MELEE_RANGE
+            }),
+        }
+    }
+}
+
+impl Action {
+    fn run(&self, agent: &mut Agent, world: &WorldView) -> BehaviourStatus {
+        match self {
+            Action::MoveTo(destination) => {
+                let position = match destination {
+                    Destination::EnemyGate => world.map_options.spawn_point(agent.team.enemy()),
+                    Destination::EnemyGoalRoom => world
+                        .map_options
+                        .goalroom_center(agent.team.enemy().home_side()),
+                    Destination::HomeGate => world.map_options.spawn_point(agent.team),
+                    Destination::HomeGoalRoom => {
+                        world.map_options.goalroom_center(agent.team.home_side())
+                    }
+                };
+
+                agent.brain.objective = NpcObjective::MoveTo(*destination);
+                agent.walk_to(position)
+            }
+            Action::CaptureLock => {
+                let target_side = agent.team.enemy().home_side();
+                let contests_locks = agent.difficulty.contests_locks();
+                let capturable = |lock: &&LockInfo| {
+                    let held_by_teammate =
+                        lock.held_by_team == Some(agent.team) && lock.held_by != Some(agent.entity);
+                    let contested = lock.held_by_team == Some(agent.team.enemy())
+                        || agent.perception.noticed_enemies().any(|enemy| {
+                            enemy.visible()
+                                && enemy.last_seen_position.distance(lock.position) < CONTEST_RADIUS
+                        });
+
+                    lock.side == target_side
+                        && !lock.unlocked
+                        && !held_by_teammate
+                        && (!contested || contests_locks)
+                        && !world.claimed_locks.iter().any(|(other, claimed)| {
+                            *other != agent.entity && *claimed == lock.entity
+                        })
+                };
+
+                // stick with the current lock until it's done, otherwise take the nearest free one
+                let current = match agent.brain.objective {
+                    NpcObjective::CaptureLock(current) => world
+                        .locks
+                        .iter()
+                        .filter(capturable)
+                        .find(|lock| lock.entity == current),
+                    _ => None,
+                };
+                let lock = current.or_else(|| {
+                    world.locks.iter().filter(capturable).min_by(|a, b| {
+                        let a = a.position.distance_squared(agent.position);
+                        let b = b.position.distance_squared(agent.position);
+                        a.total_cmp(&b)
+                    })
+                });
+
+                match lock {
+                    Some(lock) => {
+                        agent.brain.objective = NpcObjective::CaptureLock(lock.entity);
+                        agent.walk_to(lock.position);
+                        BehaviourStatus::Running
+                    }
+                    None => BehaviourStatus::Failure,
+                }
+            }
+            Action::Attack => {
+                let enemy = match agent.enemy() {
+                    Some(enemy) if enemy.visible() => enemy.last_seen_position,
+                    _ => return BehaviourStatus::Failure,
+                };
+                let kind = if enemy.distance(agent.position) <= MELEE_RANGE {
+                    AttackKind::Melee
+                } else {
+                    AttackKind::Ranged
+                };
+
+                // worse bots swing and shoot a little to either side of where they mean to
+                let miss = Quat::from_rotation_y(agent.difficulty.preset.aim_miss(agent.rng));
+                let aim = agent.position + miss * (enemy - agent.position);
+
+                agent.brain.objective = NpcObjective::Attack;
+                agent.brain.destination = None;
+                agent.attack_events.send(AttackEvent {
+                    attacker: agent.entity,
+                    kind,
+                    aim,
+                });
+                BehaviourStatus::Success
+            }
+            Action::Flee { distance } => {
+                let enemy = match agent.enemy() {
+                    Some(enemy) => enemy.last_seen_position,
+                    None => return BehaviourStatus::Failure,
+                };
+
+                let mut away = agent.position - enemy;
+                away.y = 0.0;
+                if away.length() >= *distance {
+                    return BehaviourStatus::Success;
+                }
+
+                // the path query moves this out of any wall it lands in
+                agent.brain.objective = NpcObjective::Flee;
+                agent.walk_to(enemy + away.normalize_or_zero() * *distance);
+                BehaviourStatus::Running
+            }
+            Action::Investigate => {
+                if let Some(enemy) = agent.enemy().filter(|enemy| !enemy.visible()) {
+                    agent.brain.objective = NpcObjective::Investigate;
+                    let status = agent.walk_to(enemy.last_seen_position);
+                    if status == BehaviourStatus::Success {
+                        // nobody here any more
+                        agent
+                            .perception
+                            .known_enemies
+                            .retain(|known| known.entity != enemy.entity);
+                        agent.blackboard.entities.remove("enemy");
+                    }
+                    return status;
+                }
+
+                let noise = match agent.perception.latest_noise() {
+                    Some(noise) => noise.position,
+                    None => return BehaviourStatus::Failure,
+                };
+                agent.brain.objective = NpcObjective::Investigate;
+                let status = agent.walk_to(noise);
+                if status == BehaviourStatus::Success {
+                    agent
+                        .perception
+                        .heard_noises
+                        .retain(|heard| heard.position != noise);
+                }
+                status
+            }
+            Action::Idle => {
+                agent.brain.objective = NpcObjective::Idle;
+                agent.brain.destination = None;
+                BehaviourStatus::Success
+            }
+        }
+    }
+}
+
+fn attach_behaviours(
+    mut commands: Commands,
+    library: Res<BehaviourLibrary>,
+    npcs: Query<Entity, (With<NpcPlayer>, Without<Behaviour>)>,
+) {
+    for entity in npcs.iter() {
+        commands
+            .entity(entity)
+            .insert(Behaviour {
+                tree: library.default_tree.clone(),
+            })
+            .insert(Blackboard::default());
+    }
+}
+
+fn run_behaviours(
+    time: Res<Time>,
+    library: Res<BehaviourLibrary>,
+    map_options: Res<MapOptions>,
+    robots: Query<&Team, With<Robot>>,
+    locks: Query<(Entity, &GateLock, &GateSide, &Transform)>,
+    gates: Query<&Gate>,
+    mut npcs: Query<
+        (
+            Entity,
+            &Team,
+            &Transform,
+            &Health,
+            &Behaviour,
+            &mut NpcBrain,
+            &mut Blackboard,
+            &mut Perception,
+            &BotDifficulty,
+            &mut BotRng,
+            Option<&FlagCarrier>,
+        ),
+        (With<NpcPlayer>, Without<Dead>),
+    >,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    let world = WorldView {
+        map_options: &map_options,
+        locks: locks
+            .iter()
+            .map(|(entity, lock, side, transform)| LockInfo {
+                entity,
+                side: *side,
+                position: transform.translation,
+                unlocked: lock.is_unlocked(),
+                held_by: lock.held_by(),
+                held_by_team: lock
+                    .held_by()
+                    .and_then(|holder| robots.get(holder).ok().copied()),
+            })
+            .collect(),
+        open_gates: gates
+            .iter()
+            .filter(|gate| gate.state() == GateState::Open)
+            .map(|gate| gate.side())
+            .collect(),
+        claimed_locks: npcs
+            .iter()
+            .filter_map(
+                |(entity, _, _, _, _, brain, _, _, _, _, _)| match brain.objective {
+                    NpcObjective::CaptureLock(lock) => Some((entity, lock)),
+                    _ => None,
+                },
+            )
+            .collect(),
+    };
+
+    for (
+        entity,
+        team,
+        transform,
+        health,
+        behaviour,
+        mut brain,
+        mut blackboard,
+        mut perception,
+        difficulty,
+        mut rng,
+        carrier,
+    ) in npcs.iter_mut()
+    {
+        let tree = match library.trees.get(&behaviour.tree) {
+            Some(tree) => tree,
+            None => {
+                println!("{:?} has unknown behaviour tree {}", entity, behaviour.tree);
+                continue;
+            }
+        };
+
+        for remaining in blackboard.timers.values_mut() {
+            *remaining -= time.delta_seconds();
+        }
+
+        // slower bots think less often, with some jitter so they don't all decide in step
+        brain.time_until_decision -= time.delta_seconds();
+        if brain.time_until_decision > 0.0 {
+            continue;
+        }
+        brain.time_until_decision = difficulty.preset.next_decision(&mut rng);
+
+        let previous = brain.objective;
+        // whatever leaf runs this time decides where we go, nothing running means standing still
+        brain.destination = None;
+
+        let mut agent = Agent {
+            entity,
+            team: *team,
+            position: transform.translation,
+            health: health.current / health.max,
+            carrying_flag: carrier.is_some(),
+            brain: &mut brain,
+            blackboard: &mut blackboard,
+            perception: &mut perception,
+            difficulty,
+            rng: &mut rng,
+            attack_events: &mut attack_events,
+        };
+        if tree.tick(&mut agent, &world) == BehaviourStatus::Failure {
+            agent.brain.objective = NpcObjective::Idle;
+        }
+
+        if brain.objective != previous {
+            println!("{:?} bot {:?} now {:?}", team, entity, brain.objective);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::difficulty::Difficulty;
+
+    fn node(source: &str) -> BehaviourNode {
+        ron::from_str(source).expect("bad node")
+    }
+
+    // Ticks `node` once for a bot standing at its own gate with nothing around it, returning the
+    // status and what the bot decided
+    fn tick(
+        node: &BehaviourNode,
+        carrying_flag: bool,
+        blackboard: &mut Blackboard,
+    ) -> (BehaviourStatus, NpcBrain) {
+        let mut ecs = World::new();
+        ecs.insert_resource(Events::<AttackEvent>::default());
+        let mut state = SystemState::<EventWriter<AttackEvent>>::new(&mut ecs);
+        let mut attack_events = state.get_mut(&mut ecs);
+
+        let map_options = MapOptions::default();
+        let world = WorldView {
+            map_options: &map_options,
+            locks: Vec::new(),
+            open_gates: Vec::new(),
+            claimed_locks: Vec::new(),
+        };
+        let mut brain = NpcBrain::default();
+        let mut perception = Perception::default();
+        let difficulty = BotDifficulty {
+            difficulty: Difficulty::Normal,
+            preset: Difficulty::Normal.preset(),
+            courage: 0.0,
+        };
+        let mut rng = BotRng::new(0);
+
+        let mut agent = Agent {
+            entity: Entity::from_raw(0),
+            team: Team::West,
+            position: map_options.spawn_point(Team::West),
+            health: 1.0,
+            carrying_flag,
+            brain: &mut brain,
+            blackboard,
+            perception: &mut perception,
+            difficulty: &difficulty,
+            rng: &mut rng,
+            attack_events: &mut attack_events,
+        };
+        let status = node.tick(&mut agent, &world);
+        (status, brain)
+    }
+
+    #[test]
+    fn selector_falls_through_failures() {
+        let selector = node("Selector([Condition(CarryingFlag), Action(MoveTo(EnemyGate))])");
+        let (status, brain) = tick(&selector, false, &mut Blackboard::default());
+
+        assert_eq!(status, BehaviourStatus::Running);
+        assert!(brain.destination.is_some());
+
+        let nothing_works = node("Selector([Condition(CarryingFlag), Invert(Action(Idle))])");
+        let (status, _) = tick(&nothing_works, false, &mut Blackboard::default());
+        assert_eq!(status, BehaviourStatus::Failure);
+    }
+
+    #[test]
+    fn sequence_stops_at_the_first_failure() {
+        let sequence = node("Sequence([Condition(CarryingFlag), Action(MoveTo(EnemyGate))])");
+        let (status, brain) = tick(&sequence, false, &mut Blackboard::default());
+
+        assert_eq!(status, BehaviourStatus::Failure);
+        assert_eq!(brain.destination, None);
+        assert_eq!(brain.objective, NpcObjective::Idle);
+    }
+
+    #[test]
+    fn running_passes_up_through_every_node() {
+        // the idle after the walk is never reached while the walk is still going
+        let tree = node(
+            "Selector([
+                Sequence([
+                    Condition(CarryingFlag),
+                    AlwaysSucceed(Invert(Action(MoveTo(EnemyGate)))),
+                    Action(Idle),
+                ]),
+                Action(Idle),
+            ])",
+        );
+        let (status, brain) = tick(&tree, true, &mut Blackboard::default());
+
+        assert_eq!(status, BehaviourStatus::Running);
+        assert_eq!(
+            brain.objective,
+            NpcObjective::MoveTo(Destination::EnemyGate)
+        );
+    }
+
+    #[test]
+    fn cooldown_fails_until_its_timer_runs_out() {
+        let cooldown = node(r#"Cooldown(timer: "rest", seconds: 2.0, child: Action(Idle))"#);
+        let mut blackboard = Blackboard::default();
+
+        let (status, _) = tick(&cooldown, false, &mut blackboard);
+        assert_eq!(status, BehaviourStatus::Success);
+        assert_eq!(blackboard.timers.get("rest"), Some(&2.0));
+
+        let (status, _) = tick(&cooldown, false, &mut blackboard);
+        assert_eq!(status, BehaviourStatus::Failure);
+
+        // run_behaviours counts the timers down
+        blackboard.timers.insert("rest".to_string(), 0.0);
+        let (status, _) = tick(&cooldown, false, &mut blackboard);
+        assert_eq!(status, BehaviourStatus::Success);
+    }
+
+    // Runs run_behaviours for a bot standing at its gate with an enemy in range, in fixed steps,
+    // returning what it decided every frame and where each of its attacks was aimed
+    fn replay(seed: u64, bot: u64) -> (Vec<NpcObjective>, Vec<Vec3>) {
+        let file: BehaviourFile =
+            ron::from_str(include_str!("../assets/behaviours.ron")).expect("bad behaviour file");
+        let map_options = MapOptions::default();
+        let position = map_options.spawn_point(Team::West);
+
+        let mut ecs = World::new();
+        let start = Instant::now();
+        let mut time = Time::default();
+        time.update_with_instant(start);
+        ecs.insert_resource(time);
+        ecs.insert_resource(Events::<AttackEvent>::default());
+        ecs.insert_resource(map_options);
+        ecs.insert_resource(BehaviourLibrary {
+            trees: file.trees,
+            default_tree: file.default_tree.clone(),
+        });
+
+        let enemy = ecs.spawn().id();
+        let mut perception = Perception::default();
+        perception.known_enemies.push(KnownEnemy {
+            entity: enemy,
+            last_seen_position: position + Vec3::new(6.0, 0.0, 0.0),
+            time_since_seen: 0.0,
+            time_in_sight: 1.0,
+            noticed: true,
+        });
+        let difficulty = Difficulty::Normal;
+        let npc = ecs
+            .spawn()
+            .insert_bundle((
+                NpcPlayer,
+                Team::West,
+                Transform::from_translation(position),
+                Health::new(100.0),
+                Behaviour {
+                    tree: file.default_tree,
+                },
+                NpcBrain::default(),
+                Blackboard::default(),
+                perception,
+                BotDifficulty {
+                    difficulty,
+                    preset: difficulty.preset(),
+                    courage: 0.5,
+                },
+                BotRng::for_bot(seed, bot),
+            ))
+            .id();
+
+        let mut stage = SystemStage::single_threaded().with_system(run_behaviours);
+        let mut objectives = Vec::new();
+        for frame in 1..=40 {
+            let now = start + Duration::from_secs_f32(0.25 * frame as f32);
+            ecs.resource_mut::<Time>().update_with_instant(now);
+            stage.run(&mut ecs);
+            objectives.push(ecs.get::<NpcBrain>(npc).unwrap().objective);
+        }
+
+        let events = ecs.resource::<Events<AttackEvent>>();
+        let aims = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.aim)
+            .collect();
+        (objectives, aims)
+    }
+
+    #[test]
+    fn bots_replay_the_same_match_from_the_same_seed() {
+        let (objectives, aims) = replay(42, 0);
+        assert!(objectives.contains(&NpcObjective::Attack));
+        assert!(aims.len() > 1);
+
+        assert_eq!(replay(42, 0), (objectives, aims.clone()));
+        // each bot draws from its own stream, so teammates don't miss in lockstep
+        assert_ne!(replay(42, 1).1, aims);
+        assert_ne!(replay(43, 0).1, aims);
+    }
+
+    #[test]
+    fn behaviour_file_parses() {
+        let file: BehaviourFile =
+            ron::from_str(include_str!("../assets/behaviours.ron")).expect("bad behaviour file");
+        assert!(file.trees.contains_key(&file.default_tree));
+    }
+}
@@ -1,36 +1,336 @@
-use bevy::{input::mouse::MouseWheel, prelude::*};
+use bevy::{input::mouse::MouseMotion, prelude::*};
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
 
-use crate::player::LocalPlayer;
+use crate::{
+    input_map::{Action, Actions},
+    map::MapOptions,
+    player::LocalPlayer,
+    spectator::{Spectator, SpectatorView, UpdateSpectator},
+};
+
+// Radians per second the camera swings round the player at full orbit input
+const ORBIT_SPEED: f32 = 2.0;
+// Radians the camera swings per pixel the mouse is dragged
+const DRAG_ORBIT_SPEED: f32 = 0.01;
+
+// Closest and furthest the camera gets from the player
+const MIN_DISTANCE: f32 = 30.0;
+const MAX_DISTANCE: f32 = 110.0;
+// Distance zoomed per mouse wheel notch, and per second while a zoom button is held
+const ZOOM_STEP: f32 = 8.0;
+const ZOOM_SPEED: f32 = 60.0;
+// How quickly the camera catches up with the zoom, higher is snappier
+const ZOOM_SMOOTHING: f32 = 10.0;
+
+// The camera looks down at the player from this angle above the floor
+const PITCH: f32 = std::f32::consts::FRAC_PI_4;
+
+// Further than this from where it should be, like after a respawn, the camera cuts instead of
+// panning across the arena
+const SNAP_DISTANCE: f32 = 40.0;
+
+// Units per second the free-fly camera moves at full input, at its starting zoom
+const FREE_FLY_SPEED: f32 = 40.0;
 
 pub struct KeenwatchCameraPlugin;
 
 impl Plugin for KeenwatchCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup).add_system(handle_input);
+        app.add_startup_system(setup)
+            .add_system(handle_input.label(MoveCamera).after(UpdateSpectator));
+    }
+}
+
+// Anything that adjusts the camera after it has placed itself, like cutscenes, runs after this
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MoveCamera;
+
+// How the camera sits around the player
+#[derive(Component)]
+pub struct CameraRig {
+    // Radians the camera has been swung round the player, 0 looks up the arena from +z
+    pub yaw: f32,
+    pub distance: f32,
+    // The distance being zoomed towards
+    pub target_distance: f32,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        // the same view the camera had before it could be moved
+        let distance = Vec3::new(0.0, 50.0, 50.0).length();
+        Self {
+            yaw: 0.0,
+            distance,
+            target_distance: distance,
+        }
+    }
+}
+
+// How the camera chases the robot it follows. Tweak these live in the inspector.
+#[derive(Component, Inspectable)]
+pub struct CameraFollow {
+    // The player can move this far from the middle of the view before the camera follows
+    pub dead_zone: f32,
+    // Seconds of the player's velocity the camera looks ahead by
+    pub look_ahead_time: f32,
+    pub max_look_ahead: f32,
+    // Roughly how long, in seconds, the camera takes to catch up. 0 locks it to the player.
+    pub smooth_time: f32,
+    // The robot being followed, if any
+    #[inspectable(ignore)]
+    pub target: Option<Entity>,
+    // The point on the floor the camera is looking at
+    pub focus: Vec3,
+    focus_velocity: Vec3,
+    // The first frame there is a player the camera starts on it
+    placed: bool,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self {
+            dead_zone: 2.0,
+            look_ahead_time: 0.4,
+            max_look_ahead: 6.0,
+            smooth_time: 0.25,
+            target: None,
+            focus: Vec3::ZERO,
+            focus_velocity: Vec3::ZERO,
+            placed: false,
+        }
+    }
+}
+
+impl CameraFollow {
+    // Moves the focus directly, dropping any spring motion
+    fn fly(&mut self, offset: Vec3) {
+        self.focus += offset;
+        self.focus_velocity = Vec3::ZERO;
+    }
+
+    // Moves the focus towards the robot being followed, given its position and velocity
+    fn follow(&mut self, position: Vec3, velocity: Vec3, dt: f32) {
+        let mut look_ahead = velocity * self.look_ahead_time;
+        look_ahead.y = 0.0;
+        let desired = position + look_ahead.clamp_length_max(self.max_look_ahead);
+
+        if !self.placed || self.focus.distance(desired) > SNAP_DISTANCE || self.smooth_time <= 0.0 {
+            self.placed = true;
+            self.focus = desired;
+            self.focus_velocity = Vec3::ZERO;
+            return;
+        }
+
+        // only chase the part of the offset that is outside the dead zone
+        let offset = desired - self.focus;
+        let target = self.focus + offset - offset.clamp_length_max(self.dead_zone);
+
+        self.focus = smooth_damp(
+            self.focus,
+            target,
+            &mut self.focus_velocity,
+            self.smooth_time,
+            dt,
+        );
+    }
+}
+
+// A critically damped spring towards `target` that settles in about `smooth_time` seconds. It is
+// solved per step rather than nudged by a fixed fraction, so the same motion comes out at any
+// frame rate.
+fn smooth_damp(
+    current: Vec3,
+    target: Vec3,
+    velocity: &mut Vec3,
+    smooth_time: f32,
+    dt: f32,
+) -> Vec3 {
+    let omega = 2.0 / smooth_time;
+    let x = omega * dt;
+    // a close approximation of e^-x that's cheaper and well behaved for large steps
+    let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+
+    let change = current - target;
+    let temp = (*velocity + omega * change) * dt;
+    *velocity = (*velocity - omega * temp) * decay;
+    target + (change + temp) * decay
+}
+
+impl CameraRig {
+    // Turns a direction given relative to the camera, with -z up the screen, into the world
+    pub fn relative_to_view(&self, direction: Vec3) -> Vec3 {
+        Quat::from_rotation_y(self.yaw) * direction
     }
 }
 
 fn setup(mut commands: Commands) {
-    commands.spawn_bundle(Camera3dBundle {
-        transform: Transform::from_rotation(Quat::from_rotation_x(-0.5)),
-        ..default()
-    });
+    commands
+        .spawn_bundle(Camera3dBundle {
+            transform: Transform::from_rotation(Quat::from_rotation_x(-0.5)),
+            ..default()
+        })
+        .insert(CameraRig::default())
+        .insert(CameraFollow::default());
 }
 
+// Far enough back to see the whole arena, goal rooms included
+fn overview_distance(map_options: &MapOptions) -> f32 {
+    0.85 * (map_options.wall_width + 2.0 * map_options.goalroom_length)
+}
+
+// Places the camera for whatever it is showing: the local player, or while spectating a robot,
+// the whole arena or wherever it is flown
 fn handle_input(
     time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut scroll_evr: EventReader<MouseWheel>,
-    mut camera_query: Query<&mut Transform, With<Camera>>,
-    player: Query<&Transform, (With<LocalPlayer>, Without<Camera>)>,
+    actions: Res<Actions>,
+    map_options: Res<MapOptions>,
+    spectator: Res<Spectator>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut camera_query: Query<(&mut Transform, &mut CameraRig, &mut CameraFollow), With<Camera>>,
+    local_player: Query<Entity, With<LocalPlayer>>,
+    robots: Query<(&Transform, Option<&Velocity>), Without<Camera>>,
 ) {
-    // always follow the player
-    let player_transform = player.single();
-    let mut transform = camera_query.single_mut();
-    transform.translation = player_transform.translation + Vec3::new(0.0, 50.0, 50.0);
-    transform.look_at(player_transform.translation, Vec3::Y);
-
-    // there are two additional possible inputs to control the camera
-    // 1. scroll wheel to zoom in and out
-    // 2. wasd to rotate the camera around the player
+    let (mut transform, mut rig, mut follow) = match camera_query.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let dt = time.delta_seconds();
+
+    let (view, target) = if spectator.active {
+        (spectator.view, spectator.target)
+    } else {
+        (SpectatorView::Follow, local_player.get_single().ok())
+    };
+
+    // orbit around the player with the orbit keys or stick, or by dragging the mouse
+    let dragged: f32 = mouse_motion.iter().map(|motion| motion.delta.x).sum();
+    rig.yaw += actions.value(Action::CameraOrbit) * ORBIT_SPEED * dt;
+    if actions.pressed(Action::CameraDrag) {
+        rig.yaw -= dragged * DRAG_ORBIT_SPEED;
+    }
+
+    // the wheel zooms in steps, zoom buttons zoom smoothly while held
+    let zoom = actions.delta(Action::CameraZoom) * ZOOM_STEP
+        + actions.value(Action::CameraZoom) * ZOOM_SPEED * dt;
+    rig.target_distance = (rig.target_distance - zoom).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    let target_distance = match view {
+        SpectatorView::Overview => overview_distance(&map_options),
+        _ => rig.target_distance,
+    };
+    rig.distance += (target_distance - rig.distance) * (1.0 - (-ZOOM_SMOOTHING * dt).exp());
+
+    follow.target = None;
+    match view {
+        // follow the robot a little ahead of where they're heading
+        SpectatorView::Follow => {
+            if let Some((robot_transform, velocity)) = target.and_then(|t| robots.get(t).ok()) {
+                let velocity = velocity.map_or(Vec3::ZERO, |velocity| velocity.linvel);
+                follow.target = target;
+                follow.follow(robot_transform.translation, velocity, dt);
+            }
+        }
+        SpectatorView::Overview => follow.follow(Vec3::ZERO, Vec3::ZERO, dt),
+        // forward flies away from the camera, faster when zoomed out
+        SpectatorView::FreeFly => {
+            let direction = Vec3::new(
+                actions.value(Action::MoveX),
+                0.0,
+                -actions.value(Action::MoveY),
+            );
+            let speed = FREE_FLY_SPEED * rig.distance / CameraRig::default().distance;
+            follow.fly(rig.relative_to_view(direction).clamp_length_max(1.0) * speed * dt);
+        }
+    }
+
+    let offset = Quat::from_rotation_y(rig.yaw)
+        * Quat::from_rotation_x(-PITCH)
+        * Vec3::new(0.0, 0.0, rig.distance);
+    transform.translation = follow.focus + offset;
+    transform.look_at(follow.focus, Vec3::Y);
+}
+
+// Returns the origin and direction of the ray that starts at the camera and passes through the
+// cursor, given in window coordinates. Lets systems pick points in the world with the mouse.
+pub fn cursor_ray(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    cursor_position: Vec2,
+) -> Option<(Vec3, Vec3)> {
+    let viewport_size = camera.logical_viewport_size()?;
+    let ndc = (cursor_position / viewport_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+
+    // bevy uses reversed depth, so the near plane is at 1.0
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+    Some((near, (far - near).normalize()))
+}
+
+// Where a point in the world appears on screen, in window coordinates, or None if it's behind the
+// camera. Points outside the view come back off the edges of the window.
+pub fn world_to_screen(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    world_position: Vec3,
+) -> Option<Vec2> {
+    // projecting a point behind the camera flips it through the middle of the screen
+    let view = camera_transform.compute_matrix().inverse();
+    if view.transform_point3(world_position).z >= 0.0 {
+        return None;
+    }
+
+    camera.world_to_viewport(camera_transform, world_position)
+}
+
+// Where a ray hits the flat arena floor (y = 0), if it does
+pub fn ray_ground_intersection(origin: Vec3, direction: Vec3) -> Option<Vec3> {
+    if direction.y.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let distance = -origin.y / direction.y;
+    if distance < 0.0 {
+        return None;
+    }
+
+    Some(origin + direction * distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Steps a follow camera chasing a player walking along x for a second
+    fn simulate(fps: f32) -> Vec3 {
+        let mut follow = CameraFollow::default();
+        let velocity = Vec3::new(15.0, 0.0, 0.0);
+        let dt = 1.0 / fps;
+
+        let mut position = Vec3::ZERO;
+        follow.follow(position, Vec3::ZERO, dt);
+        for _ in 0..(fps as usize) {
+            position += velocity * dt;
+            follow.follow(position, velocity, dt);
+        }
+        follow.focus
+    }
+
+    #[test]
+    fn follow_is_frame_rate_independent() {
+        let slow = simulate(30.0);
+        let fast = simulate(144.0);
+        assert!(slow.distance(fast) < 0.5, "{} vs {}", slow, fast);
+    }
+
+    #[test]
+    fn dead_zone_holds_the_camera_still() {
+        let mut follow = CameraFollow::default();
+        follow.follow(Vec3::ZERO, Vec3::ZERO, 1.0 / 60.0);
+        for _ in 0..60 {
+            follow.follow(Vec3::new(1.5, 0.0, 0.0), Vec3::ZERO, 1.0 / 60.0);
+        }
+        assert_eq!(follow.focus, Vec3::ZERO);
+    }
 }
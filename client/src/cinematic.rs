@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    camera::MoveCamera,
+    gate::{GateState, GateStateChangedEvent},
+    input_map::{Actions, SuspendReason},
+    map::MapFile,
+};
+
+// Percent of the screen height each letterbox bar covers
+const LETTERBOX_HEIGHT: f32 = 12.0;
+// How quickly the bars slide in and out, higher is snappier
+const LETTERBOX_SMOOTHING: f32 = 6.0;
+
+pub struct CinematicPlugin;
+
+impl Plugin for CinematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cinematic>()
+            .add_startup_system(setup)
+            .add_system(start_cinematics)
+            .add_system(play_cinematic.label(PlayCinematic).after(MoveCamera))
+            .add_system(animate_letterbox);
+    }
+}
+
+// Anything that adjusts the camera on top of a cinematic, like screen shake, runs after this
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlayCinematic;
+
+#[derive(Deserialize, Clone)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: Vec3,
+    pub look_at: Vec3,
+}
+
+// A camera move authored in the map file
+#[derive(Deserialize, Clone)]
+pub struct CameraPath {
+    // Seconds spent panning from the player's camera to the first keyframe, 0 cuts to it
+    pub blend_in: f32,
+    // Seconds spent panning back to the player's camera after the last keyframe
+    pub blend_out: f32,
+    // In time order, the first at time 0
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    // Where the camera is `time` seconds into the keyframes, on a curve through all of them
+    fn sample(&self, time: f32) -> Option<Transform> {
+        let keyframes = &self.keyframes;
+        let last = keyframes.len().checked_sub(1)?;
+        if last == 0 {
+            let only = &keyframes[0];
+            return Some(
+                Transform::from_translation(only.position).looking_at(only.look_at, Vec3::Y),
+            );
+        }
+
+        let i = keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.time <= time)
+            .unwrap_or(0)
+            .min(last - 1);
+        let (from, to) = (&keyframes[i], &keyframes[i + 1]);
+        let t = ((time - from.time) / (to.time - from.time).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+        let before = &keyframes[i.saturating_sub(1)];
+        let after = &keyframes[(i + 2).min(last)];
+        let position = catmull_rom(
+            before.position,
+            from.position,
+            to.position,
+            after.position,
+            t,
+        );
+        let look_at = catmull_rom(before.look_at, from.look_at, to.look_at, after.look_at, t);
+
+        Some(Transform::from_translation(position).looking_at(look_at, Vec3::Y))
+    }
+}
+
+// The point `t` of the way from p1 to p2 on a curve that also passes through p0 and p3
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+// Eases in and out, so pans don't start or stop with a jolt
+fn blend(from: &Transform, to: &Transform, t: f32) -> Transform {
+    let t = t.clamp(0.0, 1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+    Transform {
+        translation: from.translation.lerp(to.translation, t),
+        rotation: from.rotation.slerp(to.rotation, t),
+        ..*from
+    }
+}
+
+struct PlayingCinematic {
+    path: CameraPath,
+    time: f32,
+    // Where the camera was when the cinematic started
+    start: Transform,
+}
+
+// The cinematic taking over the camera, if any. Player input is suspended while one plays.
+#[derive(Default)]
+pub struct Cinematic {
+    playing: Option<PlayingCinematic>,
+}
+
+impl Cinematic {
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+}
+
+#[derive(Component)]
+struct LetterboxBar;
+
+fn setup(mut commands: Commands) {
+    for top in [true, false] {
+        let position = if top {
+            UiRect {
+                left: Val::Px(0.0),
+                top: Val::Px(0.0),
+                ..default()
+            }
+        } else {
+            UiRect {
+                left: Val::Px(0.0),
+                bottom: Val::Px(0.0),
+                ..default()
+            }
+        };
+
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(0.0)),
+                    ..default()
+                },
+                color: Color::BLACK.into(),
+                ..default()
+            })
+            .insert(LetterboxBar)
+            .insert(Name::new("Letterbox"));
+    }
+}
+
+// A gate starting to open cuts away to it, if the map has a camera path for that gate
+fn start_cinematics(
+    map_file: Res<MapFile>,
+    mut cinematic: ResMut<Cinematic>,
+    mut actions: ResMut<Actions>,
+    cameras: Query<&Transform, With<Camera>>,
+    mut gate_events: EventReader<GateStateChangedEvent>,
+) {
+    for event in gate_events.iter() {
+        if event.state != GateState::Opening || cinematic.is_playing() {
+            continue;
+        }
+
+        let path = match map_file.gate_cinematics.get(&event.side) {
+            Some(path) if !path.keyframes.is_empty() => path,
+            _ => continue,
+        };
+        let start = match cameras.get_single() {
+            Ok(transform) => *transform,
+            Err(_) => continue,
+        };
+
+        println!("Playing the {:?} gate cinematic", event.side);
+        cinematic.playing = Some(PlayingCinematic {
+            path: path.clone(),
+            time: 0.0,
+            start,
+        });
+        actions.set_suspended(SuspendReason::Cinematic, true);
+    }
+}
+
+// Runs after the camera has placed itself, so the return pan can head for where it wants to be
+fn play_cinematic(
+    time: Res<Time>,
+    mut cinematic: ResMut<Cinematic>,
+    mut actions: ResMut<Actions>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    let playing = match cinematic.playing.as_mut() {
+        Some(playing) => playing,
+        None => return,
+    };
+    let mut transform = match cameras.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    playing.time += time.delta_seconds();
+    let path = &playing.path;
+    let path_time = playing.time - path.blend_in;
+    let duration = path.duration();
+
+    let first = path.sample(0.0).unwrap_or(playing.start);
+    let last = path.sample(duration).unwrap_or(playing.start);
+
+    if path_time < 0.0 {
+        *transform = blend(&playing.start, &first, playing.time / path.blend_in);
+    } else if path_time < duration {
+        *transform = path.sample(path_time).unwrap_or(*transform);
+    } else if path_time < duration + path.blend_out {
+        let camera_pose = *transform;
+        *transform = blend(&last, &camera_pose, (path_time - duration) / path.blend_out);
+    } else {
+        cinematic.playing = None;
+        actions.set_suspended(SuspendReason::Cinematic, false);
+    }
+}
+
+fn animate_letterbox(
+    time: Res<Time>,
+    cinematic: Res<Cinematic>,
+    mut bars: Query<&mut Style, With<LetterboxBar>>,
+) {
+    let target = if cinematic.is_playing() {
+        LETTERBOX_HEIGHT
+    } else {
+        0.0
+    };
+    let catch_up = 1.0 - (-LETTERBOX_SMOOTHING * time.delta_seconds()).exp();
+
+    for mut style in bars.iter_mut() {
+        let height = match style.size.height {
+            Val::Percent(height) => height,
+            _ => 0.0,
+        };
+        style.size.height = Val::Percent(height + (target - height) * catch_up);
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    camera::cursor_ray,
+    health::Dead,
+    input_map::{Action, Actions},
+    map::Ground,
+    navigation::{NavGrid, NavPath},
+    player::LocalPlayer,
+};
+
+pub struct ClickToMovePlugin;
+
+impl Plugin for ClickToMovePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup)
+            .add_system(set_move_target)
+            .add_system(update_marker);
+    }
+}
+
+// The ring on the floor showing where the player is walking to
+#[derive(Component)]
+struct MoveTargetMarker;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Torus {
+                radius: 0.8,
+                ring_radius: 0.1,
+                subdivisions_segments: 24,
+                subdivisions_sides: 8,
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgb(0.2, 1.0, 0.4),
+                emissive: Color::rgb(0.1, 0.6, 0.2),
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(MoveTargetMarker)
+        .insert(Name::new("Move Target Marker"));
+}
+
+// Interact (right click by default) on the floor walks the player there, around walls and closed gates
+fn set_move_target(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    windows: Res<Windows>,
+    rapier_context: Res<RapierContext>,
+    nav_grid: Option<Res<NavGrid>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    grounds: Query<(), With<Ground>>,
+    player: Query<(Entity, &Transform), (With<LocalPlayer>, Without<Dead>)>,
+    mut marker: Query<
+        (&mut Transform, &mut Visibility),
+        (With<MoveTargetMarker>, Without<LocalPlayer>),
+    >,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+
+    let nav_grid = match nav_grid {
+        Some(nav_grid) => nav_grid,
+        None => return,
+    };
+    let cursor_position = match windows.get_primary().and_then(|w| w.cursor_position()) {
+        Some(cursor_position) => cursor_position,
+        None => return,
+    };
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let (origin, direction) = match cursor_ray(camera, camera_transform, cursor_position) {
+        Some(ray) => ray,
+        None => return,
+    };
+
+    // clicking on top of a wall shouldn't count as clicking the floor behind it, but robots
+    // can be clicked through
+    let filter = QueryFilter::new().exclude_sensors().exclude_dynamic();
+    let target = match rapier_context.cast_ray(origin, direction, 1000.0, true, filter) {
+        Some((entity, distance)) if grounds.contains(entity) => origin + direction * distance,
+        _ => return,
+    };
+
+    let (player_entity, player_transform) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    let waypoints = match nav_grid.find_path(player_transform.translation, target) {
+        Some(waypoints) => waypoints,
+        None => return,
+    };
+
+    commands.entity(player_entity).insert(NavPath {
+        waypoints: waypoints.into(),
+    });
+
+    for (mut marker_transform, mut visibility) in marker.iter_mut() {
+        marker_transform.translation = target + Vec3::new(0.0, 0.05, 0.0);
+        visibility.is_visible = true;
+    }
+}
+
+// Hides the marker once the path is finished or cancelled
+fn update_marker(
+    player: Query<Option<&NavPath>, With<LocalPlayer>>,
+    mut marker: Query<&mut Visibility, With<MoveTargetMarker>>,
+) {
+    let nav_path = match player.get_single() {
+        Ok(nav_path) => nav_path,
+        Err(_) => return,
+    };
+
+    if nav_path.is_some() {
+        return;
+    }
+
+    for mut visibility in marker.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
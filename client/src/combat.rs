@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    animation::{PlayAnimationEvent, RobotAnimation},
+    camera::{cursor_ray, ray_ground_intersection},
+    controller::CharacterController,
+    health::{DamageEvent, Dead, Health},
+    input_map::{Action, Actions},
+    player::LocalPlayer,
+    robot::{Robot, Team},
+    status::{ApplyStatusEffectEvent, StatusEffectKind, StatusEffects},
+};
+
+pub const MELEE_RANGE: f32 = 4.0;
+const MELEE_ARC: f32 = std::f32::consts::FRAC_PI_3 * 2.0; // 120 degrees, centered on the aim
+const MELEE_DAMAGE: f32 = 30.0;
+const MELEE_COOLDOWN: f32 = 0.8;
+const MELEE_KNOCKBACK: f32 = 250.0;
+
+const PROJECTILE_SPEED: f32 = 40.0;
+const PROJECTILE_RADIUS: f32 = 0.3;
+const PROJECTILE_DAMAGE: f32 = 15.0;
+const PROJECTILE_LIFETIME: f32 = 3.0;
+const RANGED_COOLDOWN: f32 = 0.5;
+
+// Seconds a robot keeps facing what it attacked before turning back to the way it's walking
+const ATTACK_FACING_TIME: f32 = 0.5;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AimTarget>()
+            .add_event::<AttackEvent>()
+            .add_system(update_aim)
+            .add_system(tick_cooldowns)
+            .add_system(attack_input)
+            .add_system(perform_attacks)
+            .add_system(expire_projectiles)
+            .add_system_to_stage(CoreStage::PostUpdate, handle_projectile_collisions);
+    }
+}
+
+// The point on the arena floor under the mouse cursor, if the cursor is over the floor
+#[derive(Default)]
+pub struct AimTarget(pub Option<Vec3>);
+
+// Seconds left until each attack can be used again
+#[derive(Component, Default)]
+pub struct AttackCooldowns {
+    pub melee: f32,
+    pub ranged: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttackKind {
+    Melee,
+    Ranged,
+}
+
+// Ask for `attacker` to attack towards `aim`. Cooldowns and stuns are checked when it's
+// handled, so player input and bots can send these freely.
+pub struct AttackEvent {
+    pub attacker: Entity,
+    pub kind: AttackKind,
+    pub aim: Vec3,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    owner: Entity,
+    team: Team,
+    damage: f32,
+    time_alive: f32,
+    // Set when it hits something. It's despawned at the end of the frame, and until then any
+    // other collisions it had that frame are ignored.
+    spent: bool,
+}
+
+fn update_aim(
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut aim_target: ResMut<AimTarget>,
+) {
+    aim_target.0 = None;
+
+    let cursor_position = match windows.get_primary().and_then(|w| w.cursor_position()) {
+        Some(cursor_position) => cursor_position,
+        None => return,
+    };
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    if let Some((origin, direction)) = cursor_ray(camera, camera_transform, cursor_position) {
+        aim_target.0 = ray_ground_intersection(origin, direction);
+    }
+}
+
+fn tick_cooldowns(time: Res<Time>, mut cooldowns: Query<&mut AttackCooldowns>) {
+    for mut cooldowns in cooldowns.iter_mut() {
+        cooldowns.melee = (cooldowns.melee - time.delta_seconds()).max(0.0);
+        cooldowns.ranged = (cooldowns.ranged - time.delta_seconds()).max(0.0);
+    }
+}
+
+// Attack swings at whatever is in front of the cursor, Fire sends a projectile at it.
+// This runs alongside the movement actions in player::input.
+fn attack_input(
+    actions: Res<Actions>,
+    aim_target: Res<AimTarget>,
+    player_query: Query<Entity, (With<LocalPlayer>, Without<Dead>)>,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    let aim = match aim_target.0 {
+        Some(aim) => aim,
+        None => return,
+    };
+
+    for attacker in player_query.iter() {
+        if actions.just_pressed(Action::Attack) {
+            attack_events.send(AttackEvent {
+                attacker,
+                kind: AttackKind::Melee,
+                aim,
+            });
+        }
+        if actions.just_pressed(Action::Fire) {
+            attack_events.send(AttackEvent {
+                attacker,
+                kind: AttackKind::Ranged,
+                aim,
+            });
+        }
+    }
+}
+
+fn perform_attacks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut attackers: Query<
+        (
+            &mut AttackCooldowns,
+            Option<&StatusEffects>,
+            Option<&mut CharacterController>,
+        ),
+        Without<Dead>,
+    >,
+    robots: Query<(Entity, &Transform, &Team), (With<Robot>, With<Health>)>,
+    mut attack_events: EventReader<AttackEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut status_events: EventWriter<ApplyStatusEffectEvent>,
+    mut animation_events: EventWriter<PlayAnimationEvent>,
+) {
+    for event in attack_events.iter() {
+        let (mut cooldowns, status_effects, controller) = match attackers.get_mut(event.attacker) {
+            Ok(attacker) => attacker,
+            Err(_) => continue,
+        };
+
+        if status_effects.map_or(false, |effects| effects.blocks_input()) {
+            continue;
+        }
+
+        let ready = match event.kind {
+            AttackKind::Melee => cooldowns.melee <= 0.0,
+            AttackKind::Ranged => cooldowns.ranged <= 0.0,
+        };
+        if !ready {
+            continue;
+        }
+
+        let (position, team, aim_direction) = match robots.get(event.attacker) {
+            Ok((_, transform, team)) => {
+                let mut aim_direction = event.aim - transform.translation;
+                aim_direction.y = 0.0;
+                if aim_direction.length_squared() < f32::EPSILON {
+                    continue;
+                }
+                let aim_direction = aim_direction.normalize();
+
+                (transform.translation, *team, aim_direction)
+            }
+            Err(_) => continue,
+        };
+
+        // turn to face whatever we are attacking
+        if let Some(mut controller) = controller {
+            controller.face(aim_direction, ATTACK_FACING_TIME);
+        }
+
+        animation_events.send(PlayAnimationEvent {
+            robot: event.attacker,
+            animation: RobotAnimation::Attack,
+        });
+
+        match event.kind {
+            AttackKind::Melee => {
+                cooldowns.melee = MELEE_COOLDOWN;
+
+                for (target, target_transform, target_team) in robots.iter() {
+                    if *target_team == team {
+                        continue;
+                    }
+
+                    let mut offset = target_transform.translation - position;
+                    offset.y = 0.0;
+                    if offset.length() > MELEE_RANGE {
+                        continue;
+                    }
+
+                    if offset.angle_between(aim_direction) <= 0.5 * MELEE_ARC {
+                        damage_events.send(DamageEvent {
+                            target,
+                            amount: MELEE_DAMAGE,
+                            source: Some(event.attacker),
+                        });
+                        status_events.send(ApplyStatusEffectEvent {
+                            target,
+                            kind: StatusEffectKind::Knockback,
+                            duration: 0.3,
+                            magnitude: MELEE_KNOCKBACK,
+                            origin: Some(position),
+                        });
+                    }
+                }
+            }
+            AttackKind::Ranged => {
+                cooldowns.ranged = RANGED_COOLDOWN;
+
+                // spawn the projectile clear of our own collider so it doesn't hit us
+                let spawn_position = position + aim_direction * 2.5;
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: meshes.add(Mesh::from(shape::Icosphere {
+                            radius: PROJECTILE_RADIUS,
+                            subdivisions: 2,
+                        })),
+                        material: materials.add(StandardMaterial {
+                            base_color: team.color(),
+                            emissive: team.color(),
+                            ..default()
+                        }),
+                        transform: Transform::from_translation(spawn_position),
+                        ..default()
+                    })
+                    .insert(RigidBody::Dynamic)
+                    .insert(Collider::ball(PROJECTILE_RADIUS))
+                    .insert(ColliderMassProperties::Density(0.1))
+                    .insert(GravityScale(0.0))
+                    .insert(Velocity::linear(aim_direction * PROJECTILE_SPEED))
+                    .insert(Ccd::enabled())
+                    .insert(ActiveEvents::COLLISION_EVENTS)
+                    .insert(Projectile {
+                        owner: event.attacker,
+                        team,
+                        damage: PROJECTILE_DAMAGE,
+                        time_alive: 0.0,
+                        spent: false,
+                    })
+                    .insert(Name::new("Projectile"));
+            }
+        }
+    }
+}
+
+fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectiles: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectiles.iter_mut() {
+        if projectile.spent {
+            continue;
+        }
+
+        projectile.time_alive += time.delta_seconds();
+        if projectile.time_alive > PROJECTILE_LIFETIME {
+            projectile.spent = true;
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn handle_projectile_collisions(
+    mut commands: Commands,
+    mut projectiles: Query<&mut Projectile>,
+    targets: Query<&Team, With<Health>>,
+    sensors: Query<(), With<Sensor>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for collision_event in collision_events.iter() {
+        let (entity_a, entity_b) = match collision_event {
+            CollisionEvent::Started(entity_a, entity_b, _) => (*entity_a, *entity_b),
+            CollisionEvent::Stopped(..) => continue,
+        };
+
+        let (projectile_entity, other) = if projectiles.contains(entity_a) {
+            (entity_a, entity_b)
+        } else if projectiles.contains(entity_b) {
+            (entity_b, entity_a)
+        } else {
+            continue;
+        };
+
+        // projectiles fly straight through gate locks and flags
+        if sensors.contains(other) {
+            continue;
+        }
+
+        let mut projectile = projectiles.get_mut(projectile_entity).unwrap();
+        if projectile.spent || other == projectile.owner {
+            continue;
+        }
+        projectile.spent = true;
+
+        if let Ok(team) = targets.get(other) {
+            if *team != projectile.team {
+                damage_events.send(DamageEvent {
+                    target: other,
+                    amount: projectile.damage,
+                    source: Some(projectile.owner),
+                });
+            }
+        }
+
+        commands.entity(projectile_entity).despawn_recursive();
+    }
+}
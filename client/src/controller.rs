@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    health::Dead,
+    status::{StatusEffectKind, StatusEffects},
+};
+
+pub struct CharacterControllerPlugin;
+
+impl Plugin for CharacterControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(move_characters.label(MoveCharacters));
+    }
+}
+
+// Systems that write MoveIntent should run before this, systems that want the final say over
+// a robot's velocity (dashes and the like) after it
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MoveCharacters;
+
+#[derive(Component)]
+pub struct CharacterController {
+    // Units per second at full input
+    pub max_speed: f32,
+    // Units per second squared when speeding up or turning
+    pub acceleration: f32,
+    // Units per second squared when coming to a stop
+    pub deceleration: f32,
+    // Steepest slope, in radians, that can be walked up
+    pub max_slope_angle: f32,
+    // Tallest ledge that is stepped onto instead of blocking
+    pub step_height: f32,
+    // Radians per second when turning to face the way it's moving
+    pub turn_speed: f32,
+    // A direction to face instead of the way it's moving, like towards an attack, and the
+    // seconds left to keep facing it
+    pub facing: Option<Vec3>,
+    pub facing_time: f32,
+    // Distance from the collider center down to its bottom
+    pub half_height: f32,
+    pub grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            max_speed: 15.0,
+            acceleration: 90.0,
+            deceleration: 120.0,
+            max_slope_angle: std::f32::consts::FRAC_PI_4,
+            step_height: 0.5,
+            turn_speed: 12.0,
+            facing: None,
+            facing_time: 0.0,
+            half_height: 1.5,
+            grounded: false,
+        }
+    }
+}
+
+impl CharacterController {
+    // Turns to face `direction` for the next `seconds`, whichever way the character is moving
+    pub fn face(&mut self, direction: Vec3, seconds: f32) {
+        self.facing = Some(direction);
+        self.facing_time = seconds;
+    }
+}
+
+// Where a character wants to go this frame, on the XZ plane with a length of at most 1.
+// Player input, click-to-move and bots all write this and the controller does the rest.
+#[derive(Component, Default)]
+pub struct MoveIntent(pub Vec3);
+
+#[derive(Bundle)]
+pub struct CharacterControllerBundle {
+    pub controller: CharacterController,
+    pub move_intent: MoveIntent,
+    pub rigid_body: RigidBody,
+    pub velocity: Velocity,
+    pub locked_axes: LockedAxes,
+    pub friction: Friction,
+    pub ccd: Ccd,
+}
+
+impl Default for CharacterControllerBundle {
+    fn default() -> Self {
+        Self {
+            controller: CharacterController::default(),
+            move_intent: MoveIntent::default(),
+            rigid_body: RigidBody::Dynamic,
+            velocity: Velocity::zero(),
+            // robots should never tip over, turning round is handled by the controller
+            locked_axes: LockedAxes::ROTATION_LOCKED_X | LockedAxes::ROTATION_LOCKED_Z,
+            // no friction so robots slide along walls instead of sticking to them
+            friction: Friction {
+                coefficient: 0.0,
+                combine_rule: CoefficientCombineRule::Min,
+            },
+            // stops fast robots tunnelling through walls at low frame rates
+            ccd: Ccd::enabled(),
+        }
+    }
+}
+
+// Which way round the Y axis `rotation` faces, 0 being +Z
+fn yaw(rotation: Quat) -> f32 {
+    let forward = rotation * Vec3::Z;
+    forward.x.atan2(forward.z)
+}
+
+// The shortest turn from one yaw to another, from -PI to PI
+fn yaw_difference(from: f32, to: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+// Moves `current` towards `target` by at most `max_delta`
+fn move_towards(current: Vec3, target: Vec3, max_delta: f32) -> Vec3 {
+    let delta = target - current;
+    if delta.length() <= max_delta {
+        target
+    } else {
+        current + delta.normalize() * max_delta
+    }
+}
+
+fn move_characters(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    rapier_config: Res<RapierConfiguration>,
+    mut characters: Query<
+        (
+            Entity,
+            &mut CharacterController,
+            &MoveIntent,
+            &mut Velocity,
+            &Transform,
+            Option<&StatusEffects>,
+            Option<&Dead>,
+        ),
+        With<RigidBody>,
+    >,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (entity, mut controller, intent, mut velocity, transform, status_effects, dead) in
+        characters.iter_mut()
+    {
+        // knockbacks are an impulse from rapier, let it play out instead of fighting it
+        if status_effects.map_or(false, |effects| effects.has(StatusEffectKind::Knockback)) {
+            continue;
+        }
+
+        let speed_multiplier = match dead {
+            Some(_) => 0.0,
+            None => status_effects.map_or(1.0, |effects| effects.speed_multiplier()),
+        };
+
+        let mut intent = intent.0;
+        intent.y = 0.0;
+        let mut desired = intent.clamp_length_max(1.0) * controller.max_speed * speed_multiplier;
+
+        // only collide our own queries with the level, never ourselves or other robots
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(entity)
+            .exclude_dynamic()
+            .exclude_sensors();
+
+        let ground_hit = rapier_context.cast_ray_and_get_normal(
+            transform.translation,
+            -Vec3::Y,
+            controller.half_height + 0.2,
+            true,
+            filter,
+        );
+        controller.grounded = ground_hit.is_some();
+
+        // follow slopes we can walk on, treat anything steeper like a wall
+        if let Some((_, hit)) = ground_hit {
+            let slope_angle = hit.normal.angle_between(Vec3::Y);
+            if slope_angle <= controller.max_slope_angle {
+                let along_slope = desired - hit.normal * desired.dot(hit.normal);
+                desired = along_slope.normalize_or_zero() * desired.length();
+            }
+        }
+
+        if desired != Vec3::ZERO {
+            let direction = desired.normalize();
+            let look_ahead = 1.5 + desired.length() * dt;
+
+            let wall_hit = rapier_context.cast_ray_and_get_normal(
+                transform.translation,
+                direction,
+                look_ahead,
+                true,
+                filter,
+            );
+
+            // something knee high with room above it is a step, climb it
+            let foot = transform.translation - Vec3::Y * (controller.half_height - 0.1);
+            let above_step = foot + Vec3::Y * controller.step_height;
+            let blocked_at_foot = rapier_context
+                .cast_ray(foot, direction, look_ahead, true, filter)
+                .is_some();
+            let blocked_above_step = rapier_context
+                .cast_ray(above_step, direction, look_ahead, true, filter)
+                .is_some();
+
+            if controller.grounded && blocked_at_foot && !blocked_above_step && wall_hit.is_none() {
+                // hop just high enough to clear it. Asking again while still rising doesn't hop
+                // any higher, and rapier stops the hop at anything overhead.
+                let gravity = -rapier_config.gravity.y.min(0.0);
+                let hop = (2.0 * gravity * controller.step_height).sqrt();
+                velocity.linvel.y = velocity.linvel.y.max(hop);
+            } else if let Some((_, hit)) = wall_hit {
+                // slide along the wall instead of pushing into it
+                let mut normal = hit.normal;
+                normal.y = 0.0;
+                let normal = normal.normalize_or_zero();
+                desired -= normal * desired.dot(normal).min(0.0);
+            }
+        }
+
+        let mut horizontal = Vec3::new(velocity.linvel.x, 0.0, velocity.linvel.z);
+        let rate = if desired.length_squared() >= horizontal.length_squared() {
+            controller.acceleration
+        } else {
+            controller.deceleration
+        };
+        horizontal = move_towards(horizontal, Vec3::new(desired.x, 0.0, desired.z), rate * dt);
+
+        velocity.linvel = Vec3::new(horizontal.x, velocity.linvel.y, horizontal.z);
+
+        controller.facing_time -= dt;
+        if controller.facing_time <= 0.0 {
+            controller.facing = None;
+        }
+
+        // turn to face where we are going, or what we were told to face, at a steady rate, and
+        // hold still otherwise so bumping into things doesn't spin the robot
+        let target = match controller.facing {
+            Some(facing) if dead.is_none() => Some(facing),
+            _ if intent != Vec3::ZERO && speed_multiplier > 0.0 => Some(intent),
+            _ => None,
+        };
+        velocity.angvel = Vec3::ZERO;
+        if let Some(target) = target {
+            let turn = yaw_difference(yaw(transform.rotation), target.x.atan2(target.z));
+            let max_turn = controller.turn_speed * dt;
+            velocity.angvel.y = turn.clamp(-max_turn, max_turn) / dt;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_take_the_short_way_round() {
+        use std::f32::consts::PI;
+
+        assert!((yaw_difference(0.0, PI / 2.0) - PI / 2.0).abs() < 1e-5);
+        // just past behind is a small turn the other way, not most of a circle
+        assert!((yaw_difference(PI * 0.9, -PI * 0.9) - PI * 0.2).abs() < 1e-5);
+        assert!((yaw(Quat::from_rotation_y(1.0)) - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    health::Dead,
+    map::MapOptions,
+    robot::{Robot, Team},
+};
+
+// Seconds a dropped flag sits on the ground before it returns to its goal room
+const FLAG_RETURN_TIMEOUT: f32 = 10.0;
+
+// How high above the carrier's origin the flag floats while carried
+const FLAG_CARRY_HEIGHT: f32 = 3.0;
+
+pub struct CtfPlugin;
+
+impl Plugin for CtfPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CtfScore>()
+            .add_event::<DropFlagEvent>()
+            .add_event::<FlagCapturedEvent>()
+            .add_startup_system(setup)
+            .add_system(follow_carrier)
+            .add_system(return_dropped_flags)
+            .add_system(score_captures)
+            .add_system(receive_drop_flag_event)
+            .add_system_to_stage(CoreStage::PostUpdate, handle_collisions);
+    }
+}
+
+#[derive(Default)]
+pub struct CtfScore {
+    pub east: u32,
+    pub west: u32,
+}
+
+impl CtfScore {
+    fn increment(&mut self, team: Team) {
+        match team {
+            Team::East => self.east += 1,
+            Team::West => self.west += 1,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Flag {
+    pub team: Team,
+    pub state: FlagState,
+    home: Vec3,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum FlagState {
+    Home,
+    Carried(Entity),
+    Dropped { time_since_dropped: f32 },
+}
+
+// Added to a robot while it carries an enemy flag
+#[derive(Component)]
+pub struct FlagCarrier {
+    pub flag: Entity,
+}
+
+// Send this to make a robot drop whatever flag it is carrying where it stands
+pub struct DropFlagEvent {
+    pub carrier: Entity,
+}
+
+pub struct FlagCapturedEvent {
+    pub team: Team,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    map_options: Res<MapOptions>,
+) {
+    for team in [Team::East, Team::West] {
+        let home = map_options.goalroom_center(team.home_side()) + Vec3::new(0.0, 2.0, 0.0);
+
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(0.3, 4.0, 0.3))),
+                material: materials.add(team.color().into()),
+                transform: Transform::from_translation(home),
+                ..default()
+            })
+            .insert(Collider::cylinder(2.0, 1.5))
+            .insert(Sensor)
+            .insert(Flag {
+                team,
+                state: FlagState::Home,
+                home,
+            })
+            .insert(Name::new(format!("Flag {:?}", team)));
+    }
+}
+
+fn handle_collisions(
+    mut commands: Commands,
+    robots: Query<(&Team, Option<&FlagCarrier>), (With<Robot>, Without<Dead>)>,
+    mut flags: Query<(&mut Flag, &mut Transform)>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    for collision_event in collision_events.iter() {
+        let (entity_a, entity_b) = match collision_event {
+            CollisionEvent::Started(entity_a, entity_b, _) => (*entity_a, *entity_b),
+            CollisionEvent::Stopped(..) => continue,
+        };
+
+        let (robot, flag_entity) = if robots.contains(entity_a) && flags.contains(entity_b) {
+            (entity_a, entity_b)
+        } else if robots.contains(entity_b) && flags.contains(entity_a) {
+            (entity_b, entity_a)
+        } else {
+            continue;
+        };
+
+        let (team, carrier) = robots.get(robot).unwrap();
+        let (mut flag, mut transform) = flags.get_mut(flag_entity).unwrap();
+
+        if flag.team == *team {
+            // touching your own dropped flag sends it straight home
+            if let FlagState::Dropped { .. } = flag.state {
+                println!("{:?} flag returned by {:?}", flag.team, robot);
+                flag.state = FlagState::Home;
+                transform.translation = flag.home;
+            }
+            continue;
+        }
+
+        if carrier.is_some() || matches!(flag.state, FlagState::Carried(_)) {
+            continue;
+        }
+
+        println!("{:?} flag picked up by {:?}", flag.team, robot);
+        flag.state = FlagState::Carried(robot);
+        commands
+            .entity(robot)
+            .insert(FlagCarrier { flag: flag_entity });
+    }
+}
+
+fn follow_carrier(
+    mut flags: Query<(&Flag, &mut Transform)>,
+    carriers: Query<&Transform, (With<FlagCarrier>, Without<Flag>)>,
+) {
+    for (flag, mut transform) in flags.iter_mut() {
+        if let FlagState::Carried(carrier) = flag.state {
+            if let Ok(carrier_transform) = carriers.get(carrier) {
+                transform.translation =
+                    carrier_transform.translation + Vec3::new(0.0, FLAG_CARRY_HEIGHT, 0.0);
+            }
+        }
+    }
+}
+
+fn return_dropped_flags(time: Res<Time>, mut flags: Query<(&mut Flag, &mut Transform)>) {
+    for (mut flag, mut transform) in flags.iter_mut() {
+        if let FlagState::Dropped { time_since_dropped } = flag.state {
+            let time_since_dropped = time_since_dropped + time.delta_seconds();
+            if time_since_dropped > FLAG_RETURN_TIMEOUT {
+                println!("{:?} flag returned after timeout", flag.team);
+                flag.state = FlagState::Home;
+                transform.translation = flag.home;
+            } else {
+                flag.state = FlagState::Dropped { time_since_dropped };
+            }
+        }
+    }
+}
+
+fn score_captures(
+    mut commands: Commands,
+    map_options: Res<MapOptions>,
+    mut score: ResMut<CtfScore>,
+    carriers: Query<(Entity, &Transform, &Team, &FlagCarrier)>,
+    mut flags: Query<(&mut Flag, &mut Transform), Without<FlagCarrier>>,
+    mut captured_events: EventWriter<FlagCapturedEvent>,
+) {
+    for (robot, robot_transform, team, carrier) in carriers.iter() {
+        if !map_options.goalroom_contains(team.home_side(), robot_transform.translation) {
+            continue;
+        }
+
+        // you can only score while your own flag is safe at home
+        let own_flag_home = flags
+            .iter()
+            .any(|(flag, _)| flag.team == *team && flag.state == FlagState::Home);
+        if !own_flag_home {
+            continue;
+        }
+
+        if let Ok((mut flag, mut transform)) = flags.get_mut(carrier.flag) {
+            flag.state = FlagState::Home;
+            transform.translation = flag.home;
+        }
+        commands.entity(robot).remove::<FlagCarrier>();
+
+        score.increment(*team);
+        captured_events.send(FlagCapturedEvent { team: *team });
+        println!(
+            "{:?} captured the flag! East {} - West {}",
+            team, score.east, score.west
+        );
+    }
+}
+
+fn receive_drop_flag_event(
+    mut commands: Commands,
+    carriers: Query<(&Transform, &FlagCarrier)>,
+    mut flags: Query<(&mut Flag, &mut Transform), Without<FlagCarrier>>,
+    mut drop_events: EventReader<DropFlagEvent>,
+) {
+    for event in drop_events.iter() {
+        let (carrier_transform, carrier) = match carriers.get(event.carrier) {
+            Ok(carrier) => carrier,
+            Err(_) => continue,
+        };
+
+        if let Ok((mut flag, mut transform)) = flags.get_mut(carrier.flag) {
+            println!("{:?} flag dropped by {:?}", flag.team, event.carrier);
+            flag.state = FlagState::Dropped {
+                time_since_dropped: 0.0,
+            };
+            transform.translation = Vec3::new(
+                carrier_transform.translation.x,
+                2.0,
+                carrier_transform.translation.z,
+            );
+        }
+        commands.entity(event.carrier).remove::<FlagCarrier>();
+    }
+}
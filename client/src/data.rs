@@ -0,0 +1,65 @@
+use std::{env, fs, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+// The folder the game runs from: the crate when run with cargo, otherwise next to the executable
+fn root_path() -> PathBuf {
+    env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|_| {
+            env::current_exe().map(|exe| exe.parent().map(PathBuf::from).unwrap_or_default())
+        })
+        .unwrap_or_default()
+}
+
+// Resolves a path inside the assets folder the same way bevy's AssetServer does, so hand-edited
+// data files sit next to the models and are picked up without recompiling.
+pub fn asset_path(relative: &str) -> PathBuf {
+    root_path().join("assets").join(relative)
+}
+
+// Player settings are written by the game, so they get their own folder beside the assets
+pub fn config_path(relative: &str) -> PathBuf {
+    root_path().join("config").join(relative)
+}
+
+pub fn load_ron<T: DeserializeOwned>(relative: &str) -> Result<T, String> {
+    let path = asset_path(relative);
+    let contents =
+        fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    ron::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Ok(None) when the player hasn't saved these settings yet
+pub fn load_config<T: DeserializeOwned>(relative: &str) -> Result<Option<T>, String> {
+    let path = config_path(relative);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents =
+        fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    ron::from_str(&contents)
+        .map(Some)
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn save_config<T: Serialize>(relative: &str, value: &T) -> Result<(), String> {
+    let path = config_path(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("{}: {}", parent.display(), err))?;
+    }
+
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::write(&path, contents).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Moves a config file that can't be read out of the way, so saving over it doesn't lose what
+// the player wrote. Returns where it went.
+pub fn set_aside_config(relative: &str) -> Result<PathBuf, String> {
+    let path = config_path(relative);
+    let aside = path.with_extension("ron.bad");
+    fs::rename(&path, &aside).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(aside)
+}
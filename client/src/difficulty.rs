@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+// How a bot of a given difficulty plays
+#[derive(Clone, Copy, Debug)]
+pub struct DifficultyPreset {
+    // Seconds an enemy has to stay in sight before the bot reacts to it
+    pub reaction_time: f32,
+    // Largest angle, in radians, that an attack can miss its aim by
+    pub aim_error: f32,
+    // Seconds between behaviour tree ticks, jittered a little per decision
+    pub decision_interval: f32,
+    // Chance, from 0 to 1, that a bot is the kind to walk onto a lock an enemy is holding or
+    // standing next to
+    pub lock_contest_willingness: f32,
+}
+
+impl Difficulty {
+    pub fn preset(self) -> DifficultyPreset {
+        match self {
+            Difficulty::Easy => DifficultyPreset {
+                reaction_time: 0.8,
+                aim_error: 0.35,
+                decision_interval: 0.8,
+                lock_contest_willingness: 0.2,
+            },
+            Difficulty::Normal => DifficultyPreset {
+                reaction_time: 0.4,
+                aim_error: 0.15,
+                decision_interval: 0.4,
+                lock_contest_willingness: 0.5,
+            },
+            Difficulty::Hard => DifficultyPreset {
+                reaction_time: 0.15,
+                aim_error: 0.04,
+                decision_interval: 0.15,
+                lock_contest_willingness: 0.9,
+            },
+        }
+    }
+}
+
+impl DifficultyPreset {
+    // Seconds until the next behaviour tree tick, jittered so bots don't all decide in step
+    pub fn next_decision(&self, rng: &mut BotRng) -> f32 {
+        self.decision_interval * rng.range(0.8, 1.2)
+    }
+
+    // Radians an attack swings to the side of where the bot means it to go
+    pub fn aim_miss(&self, rng: &mut BotRng) -> f32 {
+        rng.range(-self.aim_error, self.aim_error)
+    }
+}
+
+// Put on each bot when it is spawned
+#[derive(Component)]
+pub struct BotDifficulty {
+    pub difficulty: Difficulty,
+    pub preset: DifficultyPreset,
+    // Rolled once per bot, it contests locks when this is under lock_contest_willingness
+    pub courage: f32,
+}
+
+impl BotDifficulty {
+    pub fn contests_locks(&self) -> bool {
+        self.courage < self.preset.lock_contest_willingness
+    }
+}
+
+// A small splitmix64 generator. Every random choice a bot makes goes through its own one of
+// these, seeded from the lobby, so a match with the same seed plays out the same way.
+#[derive(Component, Clone)]
+pub struct BotRng {
+    state: u64,
+}
+
+impl BotRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // Each bot's own stream, split off the lobby seed by the order bots are spawned in
+    pub fn for_bot(seed: u64, bot: u64) -> Self {
+        Self::new(Self::new(seed ^ bot).next_u64())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform in [min, max)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_rolls() {
+        let mut a = BotRng::new(42);
+        let mut b = BotRng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        assert_ne!(BotRng::new(42).next_u64(), BotRng::new(43).next_u64());
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let mut rng = BotRng::new(7);
+        for _ in 0..1000 {
+            let roll = rng.next_f32();
+            assert!((0.0..1.0).contains(&roll));
+
+            let roll = rng.range(-0.5, 0.5);
+            assert!((-0.5..0.5).contains(&roll));
+        }
+    }
+
+    #[test]
+    fn harder_bots_are_sharper() {
+        let easy = Difficulty::Easy.preset();
+        let normal = Difficulty::Normal.preset();
+        let hard = Difficulty::Hard.preset();
+
+        assert!(easy.reaction_time > normal.reaction_time);
+        assert!(normal.reaction_time > hard.reaction_time);
+        assert!(easy.aim_error > normal.aim_error && normal.aim_error > hard.aim_error);
+        assert!(easy.decision_interval > hard.decision_interval);
+        assert!(easy.lock_contest_willingness < hard.lock_contest_willingness);
+    }
+}
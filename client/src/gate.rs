@@ -1,117 +1,137 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
 use bevy_rapier3d::prelude::Collider;
+use serde::Deserialize;
 
 use crate::{
     gate_lock::{GateLockUnlockEvent, LockPlugin},
-    map::MapOptions,
+    map::{MapOptions, WallBox},
+    perception::{NoiseEvent, NoiseKind},
+    shake::ScreenShake,
 };
 
+// Locks on a side that have to be unlocked before its gate opens
+pub const LOCKS_TO_OPEN: u32 = 2;
+
+// A gate grinding open can be heard from anywhere in the arena
+const OPENING_NOISE_RANGE: f32 = 200.0;
+// A gate slamming into the floor shakes the screen, less the further away it is
+const SLAM_TRAUMA: f32 = 0.6;
+const SLAM_RANGE: f32 = 150.0;
+
 pub struct GatePlugin;
 
 impl Plugin for GatePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(LockPlugin)
+            .add_event::<GateStateChangedEvent>()
             .add_startup_system(setup)
             .add_system(animate)
             .add_system(receive_gatelock_unlocked_event);
     }
 }
 
-#[derive(Inspectable, Default)]
-enum GateState {
+#[derive(Inspectable, Default, PartialEq, Clone, Copy, Debug)]
+pub enum GateState {
     Open,
     Opening,
     #[default]
     Closed,
 }
 
-#[derive(Component, Inspectable, Default, PartialEq, Clone, Copy)]
+#[derive(Component, Inspectable, Deserialize, Default, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum GateSide {
     #[default]
     East,
     West,
 }
 
+// Sent whenever a gate starts opening or finishes opening
+pub struct GateStateChangedEvent {
+    pub side: GateSide,
+    pub state: GateState,
+}
+
 #[derive(Component, Inspectable, Default)]
-struct Gate {
+pub struct Gate {
     state: GateState,
     side: GateSide,
     time_since_unlocked: f32,
     num_gates_unlocked: u32,
 }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    map_options: Res<MapOptions>,
-) {
-    let gate_east = Gate {
-        state: GateState::Closed,
-        side: GateSide::East,
-        time_since_unlocked: 0.0,
-        num_gates_unlocked: 0,
-    };
+impl Gate {
+    pub fn state(&self) -> GateState {
+        self.state
+    }
 
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_thickness - 0.1,
-                map_options.goalroom_height,
-                map_options.goalroom_width,
-            ))),
-            material: materials.add(Color::rgb(0.5, 0.0, 0.0).into()),
-            transform: Transform::from_xyz(
-                0.5 * map_options.wall_width,
-                0.5 * map_options.wall_height - 0.1,
-                0.0,
-            ),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_thickness - 0.1,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_width,
-        ))
-        .insert(gate_east)
-        .insert(Name::new("Gate East"));
-
-    let gate_west = Gate {
-        state: GateState::Closed,
-        side: GateSide::West,
-        time_since_unlocked: 0.0,
-        num_gates_unlocked: 0,
+    pub fn side(&self) -> GateSide {
+        self.side
+    }
+
+    pub fn locks_unlocked(&self) -> u32 {
+        self.num_gates_unlocked
+    }
+}
+
+// The collider of a closed gate, filling the gap in the arena wall on the given side
+pub fn gate_bounds(map_options: &MapOptions, side: GateSide) -> WallBox {
+    let x = match side {
+        GateSide::East => 0.5 * map_options.wall_width,
+        GateSide::West => 0.5 * -map_options.wall_width,
     };
 
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_thickness - 0.1,
-                map_options.goalroom_height,
-                map_options.goalroom_width,
-            ))),
-            material: materials.add(Color::rgb(0.5, 0.0, 0.0).into()),
-            transform: Transform::from_xyz(
-                0.5 * -map_options.wall_width,
-                0.5 * map_options.wall_height - 0.1,
-                0.0,
-            ),
-            ..default()
-        })
-        .insert(Collider::cuboid(
+    WallBox {
+        center: Vec3::new(x, 0.5 * map_options.wall_height - 0.1, 0.0),
+        half_extents: Vec3::new(
             0.5 * map_options.goalroom_thickness - 0.1,
             0.5 * map_options.goalroom_height,
             0.5 * map_options.goalroom_width,
-        ))
-        .insert(gate_west)
-        .insert(Name::new("Gate West"));
+        ),
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    map_options: Res<MapOptions>,
+) {
+    for (side, name) in [(GateSide::East, "Gate East"), (GateSide::West, "Gate West")] {
+        let bounds = gate_bounds(&map_options, side);
+
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(
+                    map_options.goalroom_thickness - 0.1,
+                    map_options.goalroom_height,
+                    map_options.goalroom_width,
+                ))),
+                material: materials.add(Color::rgb(0.5, 0.0, 0.0).into()),
+                transform: Transform::from_translation(bounds.center),
+                ..default()
+            })
+            .insert(Collider::cuboid(
+                bounds.half_extents.x,
+                bounds.half_extents.y,
+                bounds.half_extents.z,
+            ))
+            .insert(Gate {
+                state: GateState::Closed,
+                side,
+                time_since_unlocked: 0.0,
+                num_gates_unlocked: 0,
+            })
+            .insert(Name::new(name));
+    }
 }
 
 fn animate(
     time: Res<Time>,
     map_options: Res<MapOptions>,
+    mut screen_shake: ResMut<ScreenShake>,
     mut gates: Query<(&mut Gate, &mut Transform)>,
+    mut gate_events: EventWriter<GateStateChangedEvent>,
 ) {
     for (mut gate, mut transform) in gates.iter_mut() {
         match gate.state {
@@ -122,6 +142,11 @@ fn animate(
                 if gate.time_since_unlocked > 4.0 {
                     gate.state = GateState::Open;
                     gate.time_since_unlocked = 0.0;
+                    screen_shake.add_trauma_at(transform.translation, SLAM_TRAUMA, SLAM_RANGE);
+                    gate_events.send(GateStateChangedEvent {
+                        side: gate.side,
+                        state: gate.state,
+                    });
                     continue;
                 }
 
@@ -134,32 +159,52 @@ fn animate(
 }
 
 fn receive_gatelock_unlocked_event(
-    mut gates: Query<&mut Gate>,
+    mut gates: Query<(&mut Gate, &Transform)>,
     mut gate_lock_events: EventReader<GateLockUnlockEvent>,
+    mut gate_events: EventWriter<GateStateChangedEvent>,
+    mut noise_events: EventWriter<NoiseEvent>,
 ) {
     for event in gate_lock_events.iter() {
         match event.side {
             GateSide::East => {
-                for mut gate in gates.iter_mut() {
+                for (mut gate, transform) in gates.iter_mut() {
                     if gate.side != GateSide::East {
                         continue;
                     }
 
                     gate.num_gates_unlocked += 1;
-                    if gate.num_gates_unlocked == 2 {
+                    if gate.num_gates_unlocked == LOCKS_TO_OPEN {
                         gate.state = GateState::Opening;
+                        gate_events.send(GateStateChangedEvent {
+                            side: gate.side,
+                            state: gate.state,
+                        });
+                        noise_events.send(NoiseEvent {
+                            kind: NoiseKind::GateOpening,
+                            position: transform.translation,
+                            range: OPENING_NOISE_RANGE,
+                        });
                     }
                 }
             }
             GateSide::West => {
-                for mut gate in gates.iter_mut() {
+                for (mut gate, transform) in gates.iter_mut() {
                     if gate.side != GateSide::West {
                         continue;
                     }
 
                     gate.num_gates_unlocked += 1;
-                    if gate.num_gates_unlocked == 2 {
+                    if gate.num_gates_unlocked == LOCKS_TO_OPEN {
                         gate.state = GateState::Opening;
+                        gate_events.send(GateStateChangedEvent {
+                            side: gate.side,
+                            state: gate.state,
+                        });
+                        noise_events.send(NoiseEvent {
+                            kind: NoiseKind::GateOpening,
+                            position: transform.translation,
+                            range: OPENING_NOISE_RANGE,
+                        });
                     }
                 }
             }
@@ -1,7 +1,18 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{gate::GateSide, map::MapOptions, player::LocalPlayer};
+use crate::{
+    gate::GateSide,
+    health::{Dead, DeathEvent},
+    map::MapOptions,
+    perception::{NoiseEvent, NoiseKind},
+    robot::Robot,
+};
+
+// How far away bots hear a lock start being captured
+const CAPTURE_NOISE_RANGE: f32 = 40.0;
+// Seconds a robot has to stand on a lock to unlock it, without a speed boost
+const UNLOCK_TIME: f32 = 3.0;
 
 pub struct LockPlugin;
 
@@ -11,6 +22,8 @@ impl Plugin for LockPlugin {
             .add_startup_system(setup)
             .add_system(animate)
             .add_system(emit_gatelock_unlocked_event)
+            .add_system(release_on_death)
+            .add_system(expire_lock_speed_boosts)
             .add_system_to_stage(CoreStage::PostUpdate, handle_collisions);
     }
 }
@@ -19,6 +32,52 @@ impl Plugin for LockPlugin {
 pub struct GateLock {
     state: GateLockState,
     time_since_pressed: f32,
+    // The robot currently standing on the lock, if any
+    held_by: Option<Entity>,
+    // Progress is frozen while this is above zero
+    jammed_for: f32,
+}
+
+// Robots with this capture locks faster
+#[derive(Component)]
+pub struct LockSpeedBoost {
+    pub multiplier: f32,
+    pub remaining: f32,
+}
+
+impl GateLock {
+    // Drop any progress and go back to waiting for someone to stand on the lock
+    fn release(&mut self) {
+        self.state = GateLockState::Locked;
+        self.time_since_pressed = 0.0;
+        self.held_by = None;
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.state == GateLockState::Unlocked
+    }
+
+    pub fn held_by(&self) -> Option<Entity> {
+        self.held_by
+    }
+
+    // How far through being captured the lock is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        match self.state {
+            GateLockState::Unlocked => 1.0,
+            GateLockState::Unlocking => (self.time_since_pressed / UNLOCK_TIME).min(1.0),
+            GateLockState::Locked => 0.0,
+        }
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.jammed_for > 0.0
+    }
+
+    pub fn jam(&mut self, duration: f32) {
+        println!("Gate lock jammed for {} seconds", duration);
+        self.jammed_for = self.jammed_for.max(duration);
+    }
 }
 
 #[derive(Default, PartialEq)]
@@ -49,6 +108,8 @@ fn setup(mut commands: Commands, map_options: Res<MapOptions>) {
         .insert(GateLock {
             state: GateLockState::Locked,
             time_since_pressed: 0.0,
+            held_by: None,
+            jammed_for: 0.0,
         })
         .insert(GateSide::East)
         .insert(Name::new("Gate Lock NE"));
@@ -72,6 +133,8 @@ fn setup(mut commands: Commands, map_options: Res<MapOptions>) {
         .insert(GateLock {
             state: GateLockState::Locked,
             time_since_pressed: 0.0,
+            held_by: None,
+            jammed_for: 0.0,
         })
         .insert(GateSide::East)
         .insert(Name::new("Gate Lock SE"));
@@ -95,6 +158,8 @@ fn setup(mut commands: Commands, map_options: Res<MapOptions>) {
         .insert(GateLock {
             state: GateLockState::Locked,
             time_since_pressed: 0.0,
+            held_by: None,
+            jammed_for: 0.0,
         })
         .insert(GateSide::West)
         .insert(Name::new("Gate Lock NW"));
@@ -118,23 +183,43 @@ fn setup(mut commands: Commands, map_options: Res<MapOptions>) {
         .insert(GateLock {
             state: GateLockState::Locked,
             time_since_pressed: 0.0,
+            held_by: None,
+            jammed_for: 0.0,
         })
         .insert(GateSide::West)
         .insert(Name::new("Gate Lock SW"));
 }
 
-fn animate(time: Res<Time>, mut lights: Query<(&mut PointLight, &mut GateLock)>) {
+fn animate(
+    time: Res<Time>,
+    mut lights: Query<(&mut PointLight, &mut GateLock)>,
+    boosts: Query<&LockSpeedBoost>,
+) {
     for (mut light, mut lock) in lights.iter_mut() {
+        if lock.jammed_for > 0.0 {
+            lock.jammed_for -= time.delta_seconds();
+
+            // a jammed lock glows red and makes no progress
+            if lock.state == GateLockState::Unlocking {
+                light.color = Color::rgb(1.0, 0.0, 0.0);
+                continue;
+            }
+        }
+
         match lock.state {
             GateLockState::Unlocked => {
                 light.color = Color::rgb(1.0, 1.0, 1.0);
             }
             GateLockState::Unlocking => {
-                lock.time_since_pressed += time.delta_seconds();
+                let speed = lock
+                    .held_by
+                    .and_then(|holder| boosts.get(holder).ok())
+                    .map_or(1.0, |boost| boost.multiplier);
+                lock.time_since_pressed += time.delta_seconds() * speed;
 
                 // slowly turn green as the gate is unlocked
                 let percent_unlocked_radians =
-                    (lock.time_since_pressed / 3.0) * (std::f32::consts::PI / 2.0);
+                    (lock.time_since_pressed / UNLOCK_TIME) * (std::f32::consts::PI / 2.0);
                 light.color = Color::rgb(0.0, 0.0, 1.0)
                     + Color::rgb(0.0, 1.0, 0.0) * percent_unlocked_radians.sin()
                     + Color::rgb(0.0, 0.0, 1.0) * -percent_unlocked_radians.sin();
@@ -144,63 +229,89 @@ fn animate(time: Res<Time>, mut lights: Query<(&mut PointLight, &mut GateLock)>)
     }
 }
 
+// Any living robot standing on a lock starts capturing it, and stops when the one holding it
+// steps off
 fn handle_collisions(
-    player: Query<&Transform, With<LocalPlayer>>,
-    mut gates: Query<(&mut GateLock, &mut Visibility)>,
+    robots: Query<(), (With<Robot>, Without<Dead>)>,
+    mut gates: Query<(&mut GateLock, &mut Visibility, &Transform)>,
     mut collision_events: EventReader<CollisionEvent>,
+    mut noise_events: EventWriter<NoiseEvent>,
 ) {
     for collision_event in collision_events.iter() {
         match collision_event {
             CollisionEvent::Started(entity_a, entity_b, _) => {
-                if !player.get(*entity_a).is_ok() && !player.get(*entity_b).is_ok() {
+                let robot_entity = if robots.contains(*entity_a) {
+                    *entity_a
+                } else if robots.contains(*entity_b) {
+                    *entity_b
+                } else {
                     continue;
-                }
+                };
 
                 println!(
-                    "Player collision started between {:?} and {:?}",
+                    "Robot collision started between {:?} and {:?}",
                     entity_a, entity_b
                 );
 
-                if let Ok((mut gate_lock, mut visibility)) = gates.get_mut(*entity_a) {
+                if let Ok((mut gate_lock, mut visibility, transform)) = gates.get_mut(*entity_a) {
                     if gate_lock.state == GateLockState::Locked {
                         println!("Gate lock {:?} pressed", entity_a);
                         gate_lock.state = GateLockState::Unlocking;
+                        gate_lock.held_by = Some(robot_entity);
                         visibility.is_visible = true;
+                        noise_events.send(NoiseEvent {
+                            kind: NoiseKind::LockCaptureStarted,
+                            position: transform.translation,
+                            range: CAPTURE_NOISE_RANGE,
+                        });
                     }
                 }
 
-                if let Ok((mut gate_lock, mut visibility)) = gates.get_mut(*entity_b) {
+                if let Ok((mut gate_lock, mut visibility, transform)) = gates.get_mut(*entity_b) {
                     if gate_lock.state == GateLockState::Locked {
                         println!("Gate lock {:?} pressed", entity_b);
                         gate_lock.state = GateLockState::Unlocking;
+                        gate_lock.held_by = Some(robot_entity);
                         visibility.is_visible = true;
+                        noise_events.send(NoiseEvent {
+                            kind: NoiseKind::LockCaptureStarted,
+                            position: transform.translation,
+                            range: CAPTURE_NOISE_RANGE,
+                        });
                     }
                 }
             }
             CollisionEvent::Stopped(entity_a, entity_b, _) => {
-                if !player.get(*entity_a).is_ok() && !player.get(*entity_b).is_ok() {
+                let robot_entity = if robots.contains(*entity_a) {
+                    *entity_a
+                } else if robots.contains(*entity_b) {
+                    *entity_b
+                } else {
                     continue;
-                }
+                };
 
                 println!(
-                    "Player collision started between {:?} and {:?}",
+                    "Robot collision stopped between {:?} and {:?}",
                     entity_a, entity_b
                 );
 
-                if let Ok((mut gate_lock, mut visibility)) = gates.get_mut(*entity_a) {
-                    if gate_lock.state == GateLockState::Unlocking {
+                // someone else walking over the lock doesn't reset the holder's progress
+                if let Ok((mut gate_lock, mut visibility, _)) = gates.get_mut(*entity_a) {
+                    if gate_lock.state == GateLockState::Unlocking
+                        && gate_lock.held_by == Some(robot_entity)
+                    {
                         println!("Gate lock {:?} stopped", entity_a);
-                        gate_lock.state = GateLockState::Locked;
-                        gate_lock.time_since_pressed = 0.0;
+                        gate_lock.release();
                         visibility.is_visible = false;
                     }
                 }
 
-                if let Ok((mut gate_lock, mut visibility)) = gates.get_mut(*entity_b) {
-                    if gate_lock.state == GateLockState::Unlocking {
+                if let Ok((mut gate_lock, mut visibility, _)) = gates.get_mut(*entity_b) {
+                    if gate_lock.state == GateLockState::Unlocking
+                        && gate_lock.held_by == Some(robot_entity)
+                    {
                         println!("Gate lock {:?} stopped", entity_b);
-                        gate_lock.state = GateLockState::Locked;
-                        gate_lock.time_since_pressed = 0.0;
+                        gate_lock.release();
                         visibility.is_visible = false;
                     }
                 }
@@ -223,9 +334,38 @@ fn emit_gatelock_unlocked_event(
             continue;
         }
 
-        if lock.time_since_pressed > 3.0 {
+        if lock.time_since_pressed > UNLOCK_TIME {
             lock.state = GateLockState::Unlocked;
             event_writer.send(GateLockUnlockEvent { side: *direction });
         }
     }
 }
+
+// A robot that dies while capturing a lock lets go of it
+fn release_on_death(
+    mut locks: Query<(&mut GateLock, &mut Visibility)>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in death_events.iter() {
+        for (mut lock, mut visibility) in locks.iter_mut() {
+            if lock.state == GateLockState::Unlocking && lock.held_by == Some(event.entity) {
+                println!("Gate lock released by death of {:?}", event.entity);
+                lock.release();
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
+
+fn expire_lock_speed_boosts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut boosts: Query<(Entity, &mut LockSpeedBoost)>,
+) {
+    for (entity, mut boost) in boosts.iter_mut() {
+        boost.remaining -= time.delta_seconds();
+        if boost.remaining <= 0.0 {
+            commands.entity(entity).remove::<LockSpeedBoost>();
+        }
+    }
+}
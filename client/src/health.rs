@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    ctf::{DropFlagEvent, FlagCarrier},
+    map::MapOptions,
+    robot::{Robot, Team},
+};
+
+// Seconds a robot stays dead before it respawns
+const RESPAWN_DELAY: f32 = 5.0;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_system(apply_damage)
+            .add_system(handle_death)
+            .add_system(respawn)
+            .add_system(expire_shields);
+    }
+}
+
+#[derive(Component, Inspectable)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+}
+
+// Present on a robot from the moment it dies until it respawns
+#[derive(Component)]
+pub struct Dead {
+    respawn_timer: Timer,
+}
+
+// Cuts incoming damage by a fraction while it lasts
+#[derive(Component)]
+pub struct Shield {
+    pub damage_reduction: f32,
+    pub remaining: f32,
+}
+
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    // The entity responsible for the damage, if any
+    pub source: Option<Entity>,
+}
+
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut robots: Query<(&mut Health, Option<&Shield>), Without<Dead>>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for event in damage_events.iter() {
+        let (mut health, shield) = match robots.get_mut(event.target) {
+            Ok(robot) => robot,
+            Err(_) => continue,
+        };
+
+        // several hits can land on the same frame, only the first one to empty the bar kills
+        if health.current <= 0.0 {
+            continue;
+        }
+
+        let amount = match shield {
+            Some(shield) => event.amount * (1.0 - shield.damage_reduction),
+            None => event.amount,
+        };
+
+        health.current = (health.current - amount).max(0.0);
+        if health.current > 0.0 {
+            continue;
+        }
+
+        println!("{:?} was killed by {:?}", event.target, event.source);
+        commands.entity(event.target).insert(Dead {
+            respawn_timer: Timer::from_seconds(RESPAWN_DELAY, false),
+        });
+        death_events.send(DeathEvent {
+            entity: event.target,
+            killer: event.source,
+        });
+    }
+}
+
+fn handle_death(
+    robots: Query<Option<&FlagCarrier>, With<Robot>>,
+    mut death_events: EventReader<DeathEvent>,
+    mut drop_flag_events: EventWriter<DropFlagEvent>,
+) {
+    for event in death_events.iter() {
+        let carrier = match robots.get(event.entity) {
+            Ok(robot) => robot,
+            Err(_) => continue,
+        };
+
+        if carrier.is_some() {
+            drop_flag_events.send(DropFlagEvent {
+                carrier: event.entity,
+            });
+        }
+    }
+}
+
+fn respawn(
+    mut commands: Commands,
+    time: Res<Time>,
+    map_options: Res<MapOptions>,
+    mut robots: Query<(
+        Entity,
+        &mut Dead,
+        &mut Health,
+        &mut Transform,
+        &Team,
+        Option<&mut Velocity>,
+    )>,
+) {
+    for (entity, mut dead, mut health, mut transform, team, velocity) in robots.iter_mut() {
+        if !dead.respawn_timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        println!("{:?} respawned", entity);
+        health.current = health.max;
+        *transform =
+            Transform::from_translation(map_options.spawn_point(*team) + Vec3::new(0.0, 1.5, 0.0));
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::zero();
+        }
+        commands.entity(entity).remove::<Dead>();
+    }
+}
+
+fn expire_shields(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut shields: Query<(Entity, &mut Shield)>,
+) {
+    for (entity, mut shield) in shields.iter_mut() {
+        shield.remaining -= time.delta_seconds();
+        if shield.remaining <= 0.0 {
+            commands.entity(entity).remove::<Shield>();
+        }
+    }
+}
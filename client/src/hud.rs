@@ -0,0 +1,378 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::world_to_screen,
+    gate::{Gate, GateSide, GateState, LOCKS_TO_OPEN},
+    gate_lock::GateLock,
+    player::LocalPlayer,
+    status::{StatusEffectKind, StatusEffects},
+};
+
+const ICON_SIZE: f32 = 32.0;
+
+// The ring drawn round the lock the player is capturing, as a circle of dots that light up
+const RING_RADIUS: f32 = 36.0;
+const RING_SEGMENTS: usize = 24;
+const RING_DOT_SIZE: f32 = 8.0;
+// How far above the floor the ring sits over a lock
+const RING_HEIGHT: f32 = 3.0;
+
+// Off screen lock markers are kept this many pixels inside the edge of the window
+const MARKER_MARGIN: f32 = 24.0;
+const MARKER_SIZE: f32 = 28.0;
+
+const LOCKED_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const CAPTURING_COLOR: Color = Color::rgb(0.2, 0.4, 1.0);
+const UNLOCKED_COLOR: Color = Color::rgb(0.2, 0.9, 0.3);
+const JAMMED_COLOR: Color = Color::rgb(1.0, 0.1, 0.1);
+const EMPTY_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup)
+            .add_system(update_status_effects)
+            .add_system(update_gate_progress)
+            .add_system(update_lock_ring)
+            .add_system(add_lock_markers)
+            .add_system(update_lock_markers);
+    }
+}
+
+#[derive(Component)]
+struct StatusEffectIcon(StatusEffectKind);
+
+#[derive(Component)]
+struct GateProgressText(GateSide);
+
+#[derive(Component)]
+struct LockRing;
+
+// One of the dots around the LockRing, in order clockwise from the top
+#[derive(Component)]
+struct LockRingDot(usize);
+
+// Points to a lock while it's off screen
+#[derive(Component)]
+struct LockMarker(Entity);
+
+// How a lock is doing, as a colour
+fn lock_color(lock: &GateLock) -> Color {
+    if lock.is_unlocked() {
+        UNLOCKED_COLOR
+    } else if lock.is_jammed() {
+        JAMMED_COLOR
+    } else if lock.held_by().is_some() {
+        CAPTURING_COLOR
+    } else {
+        LOCKED_COLOR
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/DejaVuSans.ttf");
+
+    // Each gate's locks across the top of the screen
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(Name::new("HUD Gate Progress"))
+        .with_children(|parent| {
+            for side in [GateSide::West, GateSide::East] {
+                parent
+                    .spawn_bundle(
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::new(
+                                Val::Px(24.0),
+                                Val::Px(24.0),
+                                Val::Px(0.0),
+                                Val::Px(0.0),
+                            ),
+                            ..default()
+                        }),
+                    )
+                    .insert(GateProgressText(side));
+            }
+        });
+
+    // The capture ring, moved over whichever lock the player is standing on
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Px(2.0 * RING_RADIUS), Val::Px(2.0 * RING_RADIUS)),
+                display: Display::None,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(LockRing)
+        .insert(Name::new("HUD Lock Ring"))
+        .with_children(|parent| {
+            for i in 0..RING_SEGMENTS {
+                let angle = TAU * i as f32 / RING_SEGMENTS as f32;
+                let center = Vec2::new(angle.sin(), angle.cos()) * (RING_RADIUS - RING_DOT_SIZE);
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Px(RING_RADIUS + center.x - 0.5 * RING_DOT_SIZE),
+                                bottom: Val::Px(RING_RADIUS + center.y - 0.5 * RING_DOT_SIZE),
+                                ..default()
+                            },
+                            size: Size::new(Val::Px(RING_DOT_SIZE), Val::Px(RING_DOT_SIZE)),
+                            ..default()
+                        },
+                        color: EMPTY_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(LockRingDot(i));
+            }
+        });
+
+    // One icon per kind of status effect along the bottom left, hidden until it applies
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(Name::new("HUD Status Effects"))
+        .with_children(|parent| {
+            for kind in StatusEffectKind::ALL {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(ICON_SIZE), Val::Px(ICON_SIZE)),
+                            margin: UiRect::all(Val::Px(4.0)),
+                            display: Display::None,
+                            ..default()
+                        },
+                        color: kind.color().into(),
+                        ..default()
+                    })
+                    .insert(StatusEffectIcon(kind));
+            }
+        });
+}
+
+fn update_status_effects(
+    player: Query<&StatusEffects, With<LocalPlayer>>,
+    mut icons: Query<(&StatusEffectIcon, &mut Style)>,
+) {
+    let status_effects = match player.get_single() {
+        Ok(status_effects) => status_effects,
+        Err(_) => return,
+    };
+
+    for (icon, mut style) in icons.iter_mut() {
+        match status_effects.get(icon.0) {
+            Some(effect) => {
+                // icons drain as the effect runs out and widen with each stack
+                let remaining = (effect.remaining / effect.duration).clamp(0.0, 1.0);
+                style.display = Display::Flex;
+                style.size = Size::new(
+                    Val::Px(ICON_SIZE + 8.0 * (effect.stacks - 1) as f32),
+                    Val::Px(ICON_SIZE * remaining),
+                );
+            }
+            None => style.display = Display::None,
+        }
+    }
+}
+
+fn update_gate_progress(gates: Query<&Gate>, mut texts: Query<(&GateProgressText, &mut Text)>) {
+    for (progress_text, mut text) in texts.iter_mut() {
+        let gate = match gates.iter().find(|gate| gate.side() == progress_text.0) {
+            Some(gate) => gate,
+            None => continue,
+        };
+
+        text.sections[0].value = match gate.state() {
+            GateState::Closed => format!(
+                "{:?} gate: {}/{} locks",
+                gate.side(),
+                gate.locks_unlocked(),
+                LOCKS_TO_OPEN
+            ),
+            GateState::Opening => format!("{:?} gate: opening", gate.side()),
+            GateState::Open => format!("{:?} gate: open", gate.side()),
+        };
+    }
+}
+
+// Fills the ring over the lock the player is capturing, red while it's jammed
+fn update_lock_ring(
+    player: Query<Entity, With<LocalPlayer>>,
+    locks: Query<(&GateLock, &GlobalTransform)>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut rings: Query<&mut Style, With<LockRing>>,
+    mut dots: Query<(&LockRingDot, &mut UiColor)>,
+) {
+    let mut ring = match rings.get_single_mut() {
+        Ok(ring) => ring,
+        Err(_) => return,
+    };
+
+    let player = player.get_single().ok();
+    let held = locks
+        .iter()
+        .find(|(lock, _)| player.is_some() && lock.held_by() == player && !lock.is_unlocked());
+    let screen_position = held.zip(cameras.get_single().ok()).and_then(
+        |((_, lock_transform), (camera, camera_transform))| {
+            let above = lock_transform.translation() + Vec3::new(0.0, RING_HEIGHT, 0.0);
+            world_to_screen(camera, camera_transform, above)
+        },
+    );
+    let (lock, screen_position) = match held.zip(screen_position) {
+        Some(((lock, _), screen_position)) => (lock, screen_position),
+        None => {
+            ring.display = Display::None;
+            return;
+        }
+    };
+
+    ring.display = Display::Flex;
+    ring.position = UiRect {
+        left: Val::Px(screen_position.x - RING_RADIUS),
+        bottom: Val::Px(screen_position.y - RING_RADIUS),
+        ..default()
+    };
+
+    let filled = (lock.progress() * RING_SEGMENTS as f32).round() as usize;
+    let color = if lock.is_jammed() {
+        JAMMED_COLOR
+    } else {
+        CAPTURING_COLOR
+    };
+    for (dot, mut dot_color) in dots.iter_mut() {
+        *dot_color = if dot.0 < filled { color } else { EMPTY_COLOR }.into();
+    }
+}
+
+fn add_lock_markers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locks: Query<Entity, Added<GateLock>>,
+) {
+    for lock in locks.iter() {
+        commands
+            .spawn_bundle(
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/DejaVuSans.ttf"),
+                        font_size: MARKER_SIZE,
+                        color: LOCKED_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    display: Display::None,
+                    ..default()
+                }),
+            )
+            .insert(LockMarker(lock))
+            .insert(Name::new("HUD Lock Marker"));
+    }
+}
+
+// Pins an arrow to the edge of the screen in the direction of each lock that's out of view
+fn update_lock_markers(
+    windows: Res<Windows>,
+    locks: Query<(&GateLock, &GlobalTransform)>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut markers: Query<(&LockMarker, &mut Style, &mut Text)>,
+) {
+    let window_size = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width(), window.height()),
+        None => return,
+    };
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let center = 0.5 * window_size;
+    let half_extent = center - Vec2::splat(MARKER_MARGIN);
+
+    for (marker, mut style, mut text) in markers.iter_mut() {
+        let (lock, lock_transform) = match locks.get(marker.0) {
+            Ok(lock) => lock,
+            Err(_) => continue,
+        };
+
+        // a lock behind the camera is pointed at by which side of the view it's off to
+        let offset = match world_to_screen(camera, camera_transform, lock_transform.translation()) {
+            Some(screen_position) => screen_position - center,
+            None => {
+                let view = camera_transform.compute_matrix().inverse();
+                let beside = view
+                    .transform_point3(lock_transform.translation())
+                    .truncate();
+                beside.normalize_or_zero() * window_size.length()
+            }
+        };
+        if offset.x.abs() <= half_extent.x && offset.y.abs() <= half_extent.y {
+            style.display = Display::None;
+            continue;
+        }
+
+        let scale = (half_extent.x / offset.x.abs()).min(half_extent.y / offset.y.abs());
+        let edge = center + offset * scale;
+        let arrow = if offset.x.abs() * half_extent.y > offset.y.abs() * half_extent.x {
+            if offset.x > 0.0 {
+                "▶"
+            } else {
+                "◀"
+            }
+        } else if offset.y > 0.0 {
+            "▲"
+        } else {
+            "▼"
+        };
+
+        style.display = Display::Flex;
+        style.position = UiRect {
+            left: Val::Px(edge.x - 0.5 * MARKER_SIZE),
+            bottom: Val::Px(edge.y - 0.5 * MARKER_SIZE),
+            ..default()
+        };
+        text.sections[0].value = arrow.to_string();
+        text.sections[0].style.color = lock_color(lock);
+    }
+}
@@ -0,0 +1,765 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bevy::{
+    input::{
+        gamepad::{GamepadEvent, GamepadEventType},
+        mouse::{MouseScrollUnit, MouseWheel},
+        InputSystem,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{load_config, save_config, set_aside_config};
+
+// Where the player's bindings are saved, inside the config folder
+const CONFIG_FILE: &str = "input.ron";
+
+// How far an action has to be pushed to count as pressed
+const PRESS_THRESHOLD: f32 = 0.5;
+
+// Fraction of a stick's or trigger's travel ignored around rest, unless the config file says
+const STICK_DEADZONE: f32 = 0.15;
+const TRIGGER_DEADZONE: f32 = 0.1;
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputMap::load())
+            .init_resource::<Actions>()
+            .init_resource::<ActiveGamepad>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                track_gamepads.label(TrackGamepads).after(InputSystem),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, update_actions.after(TrackGamepads));
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct TrackGamepads;
+
+// Everything the player can do, independent of the keys, buttons and sticks that do it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    // Right is positive
+    MoveX,
+    // Forward, up the screen, is positive
+    MoveY,
+    // A melee swing at the cursor
+    Attack,
+    // A projectile fired at the cursor
+    Fire,
+    Ability1,
+    Ability2,
+    Ability3,
+    Ability4,
+    Ability5,
+    // Walks to whatever is under the cursor
+    Interact,
+    // Held to orbit the camera by dragging the mouse
+    CameraDrag,
+    // Zooming in is positive
+    CameraZoom,
+    // Swinging the camera round to the right is positive
+    CameraOrbit,
+    // While spectating, switch to following the next or previous robot
+    SpectateNext,
+    SpectatePrevious,
+    // While spectating, switch between following a robot, the arena overview and flying freely
+    SpectateView,
+}
+
+impl Action {
+    pub const ALL: [Action; 16] = [
+        Action::MoveX,
+        Action::MoveY,
+        Action::Attack,
+        Action::Fire,
+        Action::Ability1,
+        Action::Ability2,
+        Action::Ability3,
+        Action::Ability4,
+        Action::Ability5,
+        Action::Interact,
+        Action::CameraDrag,
+        Action::CameraZoom,
+        Action::CameraOrbit,
+        Action::SpectateNext,
+        Action::SpectatePrevious,
+        Action::SpectateView,
+    ];
+
+    // The ability actions in loadout order
+    pub const ABILITIES: [Action; 5] = [
+        Action::Ability1,
+        Action::Ability2,
+        Action::Ability3,
+        Action::Ability4,
+        Action::Ability5,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveX => "Move left / right",
+            Action::MoveY => "Move back / forward",
+            Action::Attack => "Melee attack",
+            Action::Fire => "Ranged attack",
+            Action::Ability1 => "Ability 1",
+            Action::Ability2 => "Ability 2",
+            Action::Ability3 => "Ability 3",
+            Action::Ability4 => "Ability 4",
+            Action::Ability5 => "Ability 5",
+            Action::Interact => "Walk to cursor",
+            Action::CameraDrag => "Drag to orbit camera",
+            Action::CameraZoom => "Zoom out / in",
+            Action::CameraOrbit => "Orbit camera left / right",
+            Action::SpectateNext => "Spectate next robot",
+            Action::SpectatePrevious => "Spectate previous robot",
+            Action::SpectateView => "Change spectator view",
+        }
+    }
+
+    // Names for the negative and positive directions of actions that are axes, buttons are None
+    pub fn axis_directions(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Action::MoveX => Some(("left", "right")),
+            Action::MoveY => Some(("back", "forward")),
+            Action::CameraZoom => Some(("zoom out", "zoom in")),
+            Action::CameraOrbit => Some(("orbit left", "orbit right")),
+            _ => None,
+        }
+    }
+}
+
+// Anything that is either pressed or not
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputButton {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+// Anything that reports how far it is pushed
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputAxis {
+    // Notches scrolled this frame, away from the player is positive. Read with Actions::delta.
+    MouseWheel,
+    Gamepad(GamepadAxisType),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Button(InputButton),
+    // Two buttons working as an axis, like a pair of arrow keys
+    Pair {
+        negative: InputButton,
+        positive: InputButton,
+    },
+    Axis(InputAxis),
+}
+
+impl Binding {
+    // Reports how far it moved this frame rather than where it is
+    fn is_delta(&self) -> bool {
+        matches!(self, Binding::Axis(InputAxis::MouseWheel))
+    }
+
+    // Wheels and sticks, rather than buttons
+    fn is_axis(&self) -> bool {
+        matches!(self, Binding::Axis(_))
+    }
+
+    fn uses_gamepad(&self) -> bool {
+        match self {
+            Binding::Button(button) => button.is_gamepad(),
+            Binding::Pair { negative, .. } => negative.is_gamepad(),
+            Binding::Axis(axis) => matches!(axis, InputAxis::Gamepad(_)),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Button(button) => button.label(),
+            Binding::Pair { negative, positive } => {
+                format!("{} / {}", negative.label(), positive.label())
+            }
+            Binding::Axis(InputAxis::MouseWheel) => "Mouse wheel".to_string(),
+            Binding::Axis(InputAxis::Gamepad(axis)) => format!("Pad {:?}", axis),
+        }
+    }
+}
+
+impl InputButton {
+    fn is_gamepad(&self) -> bool {
+        matches!(self, InputButton::Gamepad(_))
+    }
+
+    // Escape is kept for closing menus
+    pub fn is_bindable(&self) -> bool {
+        *self != InputButton::Key(KeyCode::Escape)
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            InputButton::Key(key) => format!("{:?}", key),
+            InputButton::Mouse(button) => format!("Mouse {:?}", button),
+            InputButton::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+// Which bindings drive each action. Saved to the config folder whenever it is changed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InputMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    #[serde(default = "default_stick_deadzone")]
+    pub stick_deadzone: f32,
+    #[serde(default = "default_trigger_deadzone")]
+    pub trigger_deadzone: f32,
+}
+
+fn default_stick_deadzone() -> f32 {
+    STICK_DEADZONE
+}
+
+fn default_trigger_deadzone() -> f32 {
+    TRIGGER_DEADZONE
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use InputButton::{Gamepad, Key, Mouse};
+
+        let pair = |negative, positive| Binding::Pair { negative, positive };
+        let bindings = [
+            (
+                Action::MoveX,
+                vec![
+                    pair(Key(KeyCode::Left), Key(KeyCode::Right)),
+                    Binding::Axis(InputAxis::Gamepad(GamepadAxisType::LeftStickX)),
+                ],
+            ),
+            (
+                Action::MoveY,
+                vec![
+                    pair(Key(KeyCode::Down), Key(KeyCode::Up)),
+                    Binding::Axis(InputAxis::Gamepad(GamepadAxisType::LeftStickY)),
+                ],
+            ),
+            (
+                Action::Attack,
+                vec![
+                    Binding::Button(Key(KeyCode::Space)),
+                    Binding::Button(Gamepad(GamepadButtonType::West)),
+                ],
+            ),
+            (
+                Action::Fire,
+                vec![
+                    Binding::Button(Mouse(MouseButton::Left)),
+                    Binding::Button(Gamepad(GamepadButtonType::RightTrigger2)),
+                ],
+            ),
+            (
+                Action::Ability1,
+                vec![
+                    Binding::Button(Key(KeyCode::Q)),
+                    Binding::Button(Gamepad(GamepadButtonType::South)),
+                ],
+            ),
+            (
+                Action::Ability2,
+                vec![
+                    Binding::Button(Key(KeyCode::E)),
+                    Binding::Button(Gamepad(GamepadButtonType::East)),
+                ],
+            ),
+            (
+                Action::Ability3,
+                vec![
+                    Binding::Button(Key(KeyCode::R)),
+                    Binding::Button(Gamepad(GamepadButtonType::North)),
+                ],
+            ),
+            (
+                Action::Ability4,
+                vec![
+                    Binding::Button(Key(KeyCode::F)),
+                    Binding::Button(Gamepad(GamepadButtonType::RightTrigger)),
+                ],
+            ),
+            (
+                Action::Ability5,
+                vec![
+                    Binding::Button(Key(KeyCode::G)),
+                    Binding::Button(Gamepad(GamepadButtonType::LeftTrigger)),
+                ],
+            ),
+            (
+                Action::Interact,
+                vec![Binding::Button(Mouse(MouseButton::Right))],
+            ),
+            (
+                Action::CameraDrag,
+                vec![Binding::Button(Mouse(MouseButton::Middle))],
+            ),
+            (
+                Action::CameraZoom,
+                vec![
+                    Binding::Axis(InputAxis::MouseWheel),
+                    pair(Key(KeyCode::S), Key(KeyCode::W)),
+                    pair(
+                        Gamepad(GamepadButtonType::DPadDown),
+                        Gamepad(GamepadButtonType::DPadUp),
+                    ),
+                ],
+            ),
+            (
+                Action::CameraOrbit,
+                vec![
+                    pair(Key(KeyCode::A), Key(KeyCode::D)),
+                    Binding::Axis(InputAxis::Gamepad(GamepadAxisType::RightStickX)),
+                ],
+            ),
+            (
+                Action::SpectateNext,
+                vec![
+                    Binding::Button(Key(KeyCode::Period)),
+                    Binding::Button(Gamepad(GamepadButtonType::DPadRight)),
+                ],
+            ),
+            (
+                Action::SpectatePrevious,
+                vec![
+                    Binding::Button(Key(KeyCode::Comma)),
+                    Binding::Button(Gamepad(GamepadButtonType::DPadLeft)),
+                ],
+            ),
+            (
+                Action::SpectateView,
+                vec![
+                    Binding::Button(Key(KeyCode::V)),
+                    Binding::Button(Gamepad(GamepadButtonType::Select)),
+                ],
+            ),
+        ];
+
+        Self {
+            bindings: bindings.into_iter().collect(),
+            stick_deadzone: STICK_DEADZONE,
+            trigger_deadzone: TRIGGER_DEADZONE,
+        }
+    }
+}
+
+impl InputMap {
+    // The saved bindings, or the defaults if there aren't any or they can't be read
+    fn load() -> Self {
+        let mut input_map = match load_config::<InputMap>(CONFIG_FILE) {
+            Ok(Some(input_map)) => input_map,
+            Ok(None) => return Self::default(),
+            Err(err) => {
+                // kept for the player to fix by hand rather than saved over at the next rebind
+                match set_aside_config(CONFIG_FILE) {
+                    Ok(aside) => println!(
+                        "Ignoring saved bindings, {}. Moved them to {}",
+                        err,
+                        aside.display()
+                    ),
+                    Err(_) => println!("Ignoring saved bindings, {}", err),
+                }
+                return Self::default();
+            }
+        };
+
+        // actions added since the file was saved start out with their default bindings
+        for (action, bindings) in Self::default().bindings {
+            input_map.bindings.entry(action).or_insert(bindings);
+        }
+
+        input_map
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = save_config(CONFIG_FILE, self) {
+            println!("Couldn't save bindings, {}", err);
+        }
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Replaces the action's binding of the same kind on the same device, keyboard and mouse or
+    // gamepad, so rebinding a key leaves the controller alone and the other way around. Buttons
+    // only ever replace buttons: the menu can't capture a wheel or a stick, so those would be
+    // lost until the bindings were reset.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        match bindings.iter_mut().find(|existing| {
+            existing.uses_gamepad() == binding.uses_gamepad()
+                && existing.is_axis() == binding.is_axis()
+        }) {
+            Some(existing) => *existing = binding,
+            None => bindings.push(binding),
+        }
+    }
+}
+
+// Something that takes gameplay input away while it's up
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SuspendReason {
+    SettingsMenu,
+    Cinematic,
+}
+
+// How far each action is pushed this frame, from -1 to 1, read by gameplay systems instead of
+// the keyboard, mouse and gamepad
+#[derive(Default)]
+pub struct Actions {
+    // While anything is in here every action reads as released. Menus and cutscenes each add
+    // themselves, so one finishing doesn't hand control back while the other is still up.
+    suspended_by: HashSet<SuspendReason>,
+    current: HashMap<Action, f32>,
+    previous: HashMap<Action, f32>,
+    deltas: HashMap<Action, f32>,
+}
+
+impl Actions {
+    pub fn set_suspended(&mut self, by: SuspendReason, suspended: bool) {
+        if suspended {
+            self.suspended_by.insert(by);
+        } else {
+            self.suspended_by.remove(&by);
+        }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        !self.suspended_by.is_empty()
+    }
+
+    // How far the action's relative bindings, like the mouse wheel, moved this frame. These don't
+    // count towards value, which is how far it is held.
+    pub fn delta(&self, action: Action) -> f32 {
+        self.deltas.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn value(&self, action: Action) -> f32 {
+        self.current.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn previous_value(&self, action: Action) -> f32 {
+        self.previous.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action).abs() >= PRESS_THRESHOLD
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed(action) && self.previous_value(action).abs() < PRESS_THRESHOLD
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.pressed(action) && self.previous_value(action).abs() >= PRESS_THRESHOLD
+    }
+}
+
+// The controller that plays. Only one does, the first plugged in, and when it's unplugged the
+// next one still connected takes over.
+#[derive(Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+// The devices bindings are read from this frame
+struct Devices<'a> {
+    keyboard: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    gamepad_buttons: &'a Input<GamepadButton>,
+    gamepad_axes: &'a Axis<GamepadAxis>,
+    gamepad: Option<Gamepad>,
+    scroll: f32,
+    stick_deadzone: f32,
+    trigger_deadzone: f32,
+}
+
+impl Devices<'_> {
+    fn pressed(&self, button: InputButton) -> bool {
+        match button {
+            InputButton::Key(key) => self.keyboard.pressed(key),
+            InputButton::Mouse(button) => self.mouse.pressed(button),
+            InputButton::Gamepad(button_type) => self.gamepad.map_or(false, |gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton::new(gamepad, button_type))
+            }),
+        }
+    }
+
+    fn axis(&self, axis: InputAxis) -> f32 {
+        match axis {
+            InputAxis::MouseWheel => self.scroll,
+            InputAxis::Gamepad(axis_type) => match stick_axes(axis_type) {
+                // a stick's deadzone is a circle, so both of its axes are needed to read either
+                Some((x_axis, y_axis)) => {
+                    let stick = Vec2::new(self.gamepad_axis(x_axis), self.gamepad_axis(y_axis));
+                    let stick = radial_deadzone(stick, self.stick_deadzone);
+                    if axis_type == x_axis {
+                        stick.x
+                    } else {
+                        stick.y
+                    }
+                }
+                None => axial_deadzone(self.gamepad_axis(axis_type), self.trigger_deadzone),
+            },
+        }
+    }
+
+    fn gamepad_axis(&self, axis_type: GamepadAxisType) -> f32 {
+        self.gamepad
+            .and_then(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis_type)))
+            .unwrap_or(0.0)
+    }
+
+    fn value(&self, binding: &Binding) -> f32 {
+        let button_value = |button| if self.pressed(button) { 1.0 } else { 0.0 };
+        match *binding {
+            Binding::Button(button) => button_value(button),
+            Binding::Pair { negative, positive } => button_value(positive) - button_value(negative),
+            Binding::Axis(axis) => self.axis(axis),
+        }
+    }
+}
+
+// The X and Y axes of the stick an axis belongs to, None for triggers
+fn stick_axes(axis_type: GamepadAxisType) -> Option<(GamepadAxisType, GamepadAxisType)> {
+    match axis_type {
+        GamepadAxisType::LeftStickX | GamepadAxisType::LeftStickY => {
+            Some((GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY))
+        }
+        GamepadAxisType::RightStickX | GamepadAxisType::RightStickY => {
+            Some((GamepadAxisType::RightStickX, GamepadAxisType::RightStickY))
+        }
+        _ => None,
+    }
+}
+
+// Ignores the stick near its center and rescales the rest, so pushing just past the deadzone
+// moves slowly instead of jumping to the deadzone's speed. Never longer than 1.
+fn radial_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick * (scaled / length)
+}
+
+fn axial_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value.abs() <= deadzone {
+        return 0.0;
+    }
+
+    value.signum() * ((value.abs() - deadzone) / (1.0 - deadzone)).min(1.0)
+}
+
+// Picks up controllers as they're plugged in and moves on to another when the one playing is
+// unplugged
+fn track_gamepads(
+    gamepads: Res<Gamepads>,
+    mut active_gamepad: ResMut<ActiveGamepad>,
+    mut gamepad_events: EventReader<GamepadEvent>,
+) {
+    for event in gamepad_events.iter() {
+        match event.event_type {
+            GamepadEventType::Connected if active_gamepad.0.is_none() => {
+                println!("Using gamepad {:?}", event.gamepad);
+                active_gamepad.0 = Some(event.gamepad);
+            }
+            GamepadEventType::Disconnected if active_gamepad.0 == Some(event.gamepad) => {
+                active_gamepad.0 = gamepads
+                    .iter()
+                    .find(|gamepad| **gamepad != event.gamepad)
+                    .copied();
+                println!(
+                    "Gamepad {:?} disconnected, now using {:?}",
+                    event.gamepad, active_gamepad.0
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+// Trackpads scroll by the pixel, many times a gesture, where a mouse wheel moves a line per
+// notch
+const PIXELS_PER_NOTCH: f32 = 100.0;
+
+// How far a scroll event turned the wheel, in notches however the device measures it
+fn scroll_notches(event: &MouseWheel) -> f32 {
+    match event.unit {
+        MouseScrollUnit::Line => event.y,
+        MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+    }
+}
+
+fn update_actions(
+    input_map: Res<InputMap>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    active_gamepad: Res<ActiveGamepad>,
+    mut scroll_events: EventReader<MouseWheel>,
+    mut actions: ResMut<Actions>,
+) {
+    let devices = Devices {
+        keyboard: &keyboard_input,
+        mouse: &mouse_input,
+        gamepad_buttons: &gamepad_buttons,
+        gamepad_axes: &gamepad_axes,
+        gamepad: active_gamepad.0,
+        scroll: scroll_events.iter().map(scroll_notches).sum(),
+        stick_deadzone: input_map.stick_deadzone,
+        trigger_deadzone: input_map.trigger_deadzone,
+    };
+
+    actions.previous = std::mem::take(&mut actions.current);
+    actions.deltas.clear();
+    if actions.is_suspended() {
+        return;
+    }
+
+    for (action, bindings) in input_map.bindings.iter() {
+        // several bindings held at once add up, but never past fully pushed
+        let value = bindings
+            .iter()
+            .filter(|binding| !binding.is_delta())
+            .map(|binding| devices.value(binding))
+            .sum::<f32>()
+            .clamp(-1.0, 1.0);
+        if value.abs() > f32::EPSILON {
+            actions.current.insert(*action, value);
+        }
+
+        let delta = bindings
+            .iter()
+            .filter(|binding| binding.is_delta())
+            .map(|binding| devices.value(binding))
+            .sum::<f32>();
+        if delta.abs() > f32::EPSILON {
+            actions.deltas.insert(*action, delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_survive_a_round_trip() {
+        let input_map = InputMap::default();
+        let saved = ron::to_string(&input_map).unwrap();
+        let loaded: InputMap = ron::from_str(&saved).unwrap();
+
+        assert_eq!(loaded.bindings, input_map.bindings);
+    }
+
+    #[test]
+    fn any_key_but_escape_can_be_bound_and_saved() {
+        let mut input_map = InputMap::default();
+        input_map.rebind(
+            Action::Attack,
+            Binding::Button(InputButton::Key(KeyCode::F1)),
+        );
+        input_map.rebind(
+            Action::Fire,
+            Binding::Button(InputButton::Key(KeyCode::NumpadEnter)),
+        );
+        let saved = ron::to_string(&input_map).unwrap();
+        let loaded: InputMap = ron::from_str(&saved).unwrap();
+        assert_eq!(loaded.bindings, input_map.bindings);
+
+        assert!(InputButton::Key(KeyCode::NumpadAdd).is_bindable());
+        assert!(!InputButton::Key(KeyCode::Escape).is_bindable());
+    }
+
+    #[test]
+    fn rebinding_keeps_the_other_device() {
+        let mut input_map = InputMap::default();
+        let key = Binding::Button(InputButton::Key(KeyCode::Z));
+        input_map.rebind(Action::Attack, key);
+
+        let bindings = input_map.get(Action::Attack);
+        assert_eq!(bindings[0], key);
+        assert_eq!(
+            bindings[1],
+            Binding::Button(InputButton::Gamepad(GamepadButtonType::West))
+        );
+    }
+
+    #[test]
+    fn rebinding_zoom_keys_keeps_the_wheel() {
+        let mut input_map = InputMap::default();
+        let keys = Binding::Pair {
+            negative: InputButton::Key(KeyCode::Q),
+            positive: InputButton::Key(KeyCode::E),
+        };
+        input_map.rebind(Action::CameraZoom, keys);
+
+        let bindings = input_map.get(Action::CameraZoom);
+        assert!(bindings.contains(&Binding::Axis(InputAxis::MouseWheel)));
+        assert!(bindings.contains(&keys));
+        assert_eq!(bindings.len(), 3);
+    }
+
+    #[test]
+    fn trackpad_pixels_scroll_like_notches() {
+        let notch = MouseWheel {
+            unit: MouseScrollUnit::Line,
+            x: 0.0,
+            y: 1.0,
+        };
+        let swipe = MouseWheel {
+            unit: MouseScrollUnit::Pixel,
+            x: 0.0,
+            y: 40.0,
+        };
+
+        assert_eq!(scroll_notches(&notch), 1.0);
+        assert!(scroll_notches(&swipe) < 1.0);
+    }
+
+    #[test]
+    fn sticks_ignore_their_center_and_never_overshoot() {
+        assert_eq!(radial_deadzone(Vec2::new(0.1, 0.05), 0.15), Vec2::ZERO);
+
+        // just past the deadzone is a gentle push, not a jump to 15%
+        let gentle = radial_deadzone(Vec2::new(0.2, 0.0), 0.15);
+        assert!(gentle.x > 0.0 && gentle.x < 0.1);
+
+        // half way along the diagonal stays on the diagonal
+        let diagonal = radial_deadzone(Vec2::new(0.5, 0.5), 0.15);
+        assert!((diagonal.x - diagonal.y).abs() < 1e-6);
+
+        // square-ish stick hardware can report corners past 1
+        assert!(radial_deadzone(Vec2::new(1.0, 1.0), 0.15).length() <= 1.0 + 1e-6);
+        assert_eq!(axial_deadzone(-1.0, 0.1), -1.0);
+        assert_eq!(axial_deadzone(0.05, 0.1), 0.0);
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert!(InputButton::try_from("Key(Nope)".to_string()).is_err());
+        assert!(InputAxis::try_from("Gamepad(LeftStickZ)".to_string()).is_err());
+        assert_eq!(
+            InputButton::try_from("Mouse(Right)".to_string()),
+            Ok(InputButton::Mouse(MouseButton::Right))
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    data::{load_config, load_ron},
+    difficulty::Difficulty,
+    robot::Team,
+    skin::RobotLook,
+};
+
+// Where the player's own match setup is kept, inside the config folder
+const CONFIG_FILE: &str = "lobby.ron";
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Lobby::load());
+    }
+}
+
+// How the match is set up, read by everything that spawns a robot
+#[derive(Deserialize)]
+pub struct Lobby {
+    pub per_team: usize,
+    // Every bot's random choices are drawn from this, so the same seed plays out the same way
+    pub seed: u64,
+    pub east_bots: Vec<Difficulty>,
+    pub west_bots: Vec<Difficulty>,
+    // How the local player's robot is dressed
+    pub player_look: RobotLook,
+}
+
+impl Lobby {
+    // The player's saved setup, or the default match if there isn't one or it can't be read
+    fn load() -> Self {
+        match load_config::<Lobby>(CONFIG_FILE) {
+            Ok(Some(lobby)) => return lobby,
+            Ok(None) => {}
+            Err(err) => println!("Ignoring saved lobby, {}", err),
+        }
+
+        load_ron("lobby.ron").unwrap_or_else(|err| panic!("bad lobby file {}", err))
+    }
+
+    // Bots past the end of their team's list play on Normal
+    pub fn bot_difficulty(&self, team: Team, bot: usize) -> Difficulty {
+        let bots = match team {
+            Team::East => &self.east_bots,
+            Team::West => &self.west_bots,
+        };
+        bots.get(bot).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_lobby_parses() {
+        let lobby: Lobby =
+            ron::from_str(include_str!("../assets/lobby.ron")).expect("bad lobby file");
+
+        assert_eq!(lobby.bot_difficulty(Team::East, 1), Difficulty::Normal);
+        // the list is shorter than the team
+        assert_eq!(lobby.bot_difficulty(Team::West, 1), Difficulty::Normal);
+    }
+}
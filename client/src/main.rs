@@ -2,22 +2,66 @@ use bevy::{app::PluginGroupBuilder, prelude::*};
 use bevy_inspector_egui::WorldInspectorPlugin;
 use bevy_rapier3d::prelude::*;
 
+mod ability;
+mod animation;
+mod behaviour;
 mod camera;
+mod cinematic;
+mod click_to_move;
+mod combat;
+mod controller;
+mod ctf;
+mod data;
+mod difficulty;
 mod gate;
 mod gate_lock;
+mod health;
+mod hud;
+mod input_map;
+mod lobby;
 mod map;
+mod navigation;
 mod npc;
+mod occlusion;
+mod perception;
 mod player;
+mod robot;
+mod settings_menu;
+mod shake;
+mod skin;
+mod spectator;
+mod status;
 
 pub struct KeenwatchPluginGroup;
 
 impl PluginGroup for KeenwatchPluginGroup {
     fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group.add(input_map::InputMapPlugin);
+        group.add(lobby::LobbyPlugin);
+        group.add(robot::RobotPlugin);
+        group.add(skin::SkinPlugin);
         group.add(player::PlayerPlugin);
+        group.add(animation::RobotAnimationPlugin);
+        group.add(controller::CharacterControllerPlugin);
         group.add(map::MapPlugin);
         group.add(camera::KeenwatchCameraPlugin);
+        group.add(occlusion::OcclusionPlugin);
+        group.add(spectator::SpectatorPlugin);
+        group.add(cinematic::CinematicPlugin);
+        group.add(shake::ScreenShakePlugin);
         group.add(gate::GatePlugin);
         group.add(npc::NpcPlugin);
+        group.add(behaviour::BehaviourPlugin);
+        group.add(perception::PerceptionPlugin);
+        group.add(ctf::CtfPlugin);
+        group.add(health::HealthPlugin);
+        group.add(combat::CombatPlugin);
+        group.add(ability::AbilityPlugin);
+        group.add(status::StatusEffectPlugin);
+        group.add(hud::HudPlugin);
+        group.add(settings_menu::SettingsMenuPlugin);
+        group.add(navigation::NavigationPlugin);
+        group.add(click_to_move::ClickToMovePlugin);
     }
 }
 
@@ -28,38 +72,5 @@ fn main() {
         .add_plugin(RapierDebugRenderPlugin::default())
         .add_plugin(WorldInspectorPlugin::new())
         .add_plugins(KeenwatchPluginGroup)
-        .add_system(link_animations) // see below
         .run();
 }
-
-// Going to choose to keep hacks in main so that I can easily remove them later
-
-// This solves a problem with multiple AnimationPlayers. You can't query the AnimationPlayer component
-// along with Player components so you need to tie the AnimationPlayer to the Player entity using
-// an additional component.
-// See: https://github.com/bevyengine/bevy/discussions/5564
-#[derive(Component)]
-pub struct AnimationEntityLink(pub Entity);
-
-fn get_top_parent(mut curr_entity: Entity, parent_query: &Query<&Parent>) -> Entity {
-    loop {
-        if let Ok(parent) = parent_query.get(curr_entity) {
-            curr_entity = parent.get();
-        } else {
-            break;
-        }
-    }
-    curr_entity
-}
-
-fn link_animations(
-    player_query: Query<Entity, Added<AnimationPlayer>>,
-    parent_query: Query<&Parent>,
-    mut commands: Commands,
-) {
-    for entity in player_query.iter() {
-        commands
-            .entity(get_top_parent(entity, &parent_query))
-            .insert(AnimationEntityLink(entity.clone()));
-    }
-}
@@ -1,30 +1,40 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{cinematic::CameraPath, data::load_ron, gate::GateSide, robot::Team};
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(MapOptions {
-            ground_color: Color::rgb(112.0 / 256.0, 128.0 / 256.0, 144.0 / 256.0),
-            ground_length: 100.0,
-            ground_width: 125.0,
-
-            wall_color: Color::rgb(0.3, 0.3, 0.3),
-            wall_length: 100.0,
-            wall_width: 125.0,
-            wall_height: 15.0,
-            wall_thickness: 1.0,
+        let file: MapFile =
+            load_ron("map.ron").unwrap_or_else(|err| panic!("bad map file {}", err));
 
-            goalroom_length: 30.0,
-            goalroom_width: 30.0,
-            goalroom_height: 15.0,
-            goalroom_thickness: 1.0,
-        })
-        .add_startup_system(setup);
+        app.insert_resource(MapOptions::default())
+            .insert_resource(file)
+            .add_startup_system(setup);
     }
 }
 
+// Everything about the map that designers author in assets/map.ron
+#[derive(Deserialize)]
+pub struct MapFile {
+    // Played when a gate starts opening, gates without one don't cut away
+    #[serde(default)]
+    pub gate_cinematics: HashMap<GateSide, CameraPath>,
+}
+
+// Marks the walkable floor colliders
+#[derive(Component)]
+pub struct Ground;
+
+// Marks the arena and goal room walls
+#[derive(Component)]
+pub struct Wall;
+
 pub struct MapOptions {
     ground_color: Color,
     ground_length: f32,
@@ -42,6 +52,122 @@ pub struct MapOptions {
     pub goalroom_thickness: f32,
 }
 
+impl Default for MapOptions {
+    fn default() -> Self {
+        Self {
+            ground_color: Color::rgb(112.0 / 256.0, 128.0 / 256.0, 144.0 / 256.0),
+            ground_length: 100.0,
+            ground_width: 125.0,
+
+            wall_color: Color::rgb(0.3, 0.3, 0.3),
+            wall_length: 100.0,
+            wall_width: 125.0,
+            wall_height: 15.0,
+            wall_thickness: 1.0,
+
+            goalroom_length: 30.0,
+            goalroom_width: 30.0,
+            goalroom_height: 15.0,
+            goalroom_thickness: 1.0,
+        }
+    }
+}
+
+// A solid box in the level, as spawned with a cuboid collider
+#[derive(Clone, Copy, Debug)]
+pub struct WallBox {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+}
+
+impl MapOptions {
+    // The arena walls and the walls around both goal rooms, leaving gaps for the gates
+    pub fn walls(&self) -> Vec<WallBox> {
+        let mut walls = Vec::new();
+
+        // Front and back
+        for z in [0.5 * self.wall_length, -0.5 * self.wall_length] {
+            walls.push(WallBox {
+                center: Vec3::new(0.0, 0.5 * self.wall_height, z),
+                half_extents: Vec3::new(
+                    0.5 * self.wall_width,
+                    0.5 * self.wall_height,
+                    0.5 * self.wall_thickness,
+                ),
+            });
+        }
+
+        // Left and right, either side of the gate
+        for x in [-0.5 * self.wall_width, 0.5 * self.wall_width] {
+            for z in [0.3 * self.wall_length, -0.3 * self.wall_length] {
+                walls.push(WallBox {
+                    center: Vec3::new(x, 0.5 * self.wall_height, z),
+                    half_extents: Vec3::new(
+                        0.5 * self.wall_thickness,
+                        0.5 * self.wall_height,
+                        0.2 * self.wall_length,
+                    ),
+                });
+            }
+        }
+
+        // Goal rooms, three walls each with the open side facing the gate
+        for side in [GateSide::East, GateSide::West] {
+            let center = self.goalroom_center(side) + Vec3::Y * 0.5 * self.goalroom_height;
+            let outwards = match side {
+                GateSide::East => 1.0,
+                GateSide::West => -1.0,
+            };
+
+            for z in [0.5 * self.goalroom_length, -0.5 * self.goalroom_length] {
+                walls.push(WallBox {
+                    center: center + Vec3::Z * z,
+                    half_extents: Vec3::new(
+                        0.5 * self.goalroom_length,
+                        0.5 * self.goalroom_height,
+                        0.5 * self.goalroom_thickness,
+                    ),
+                });
+            }
+            walls.push(WallBox {
+                center: center + Vec3::X * outwards * 0.5 * self.goalroom_width,
+                half_extents: Vec3::new(
+                    0.5 * self.goalroom_thickness,
+                    0.5 * self.goalroom_height,
+                    0.5 * self.goalroom_length,
+                ),
+            });
+        }
+
+        walls
+    }
+
+    // Center of the goal room floor on the given side
+    pub fn goalroom_center(&self, side: GateSide) -> Vec3 {
+        let x = 0.5 * self.wall_width + 0.5 * self.goalroom_width;
+        match side {
+            GateSide::East => Vec3::new(x, 0.0, 0.0),
+            GateSide::West => Vec3::new(-x, 0.0, 0.0),
+        }
+    }
+
+    // True when the point is inside the goal room on the given side, ignoring height
+    pub fn goalroom_contains(&self, side: GateSide, point: Vec3) -> bool {
+        let center = self.goalroom_center(side);
+        (point.x - center.x).abs() < 0.5 * self.goalroom_width
+            && (point.z - center.z).abs() < 0.5 * self.goalroom_length
+    }
+
+    // Where robots of a team come back into the arena, just inside their own gate
+    pub fn spawn_point(&self, team: Team) -> Vec3 {
+        let x = 0.5 * self.wall_width - 5.0;
+        match team.home_side() {
+            GateSide::East => Vec3::new(x, 0.0, 0.0),
+            GateSide::West => Vec3::new(-x, 0.0, 0.0),
+        }
+    }
+}
+
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -76,305 +202,46 @@ fn setup(
             (5.0 / 2.0) * map_options.ground_width,
             0.0,
             (5.0 / 2.0) * map_options.ground_length,
-        ));
+        ))
+        .insert(Ground);
 
     // Walls
+    for wall in map_options.walls() {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(
+                    2.0 * wall.half_extents.x,
+                    2.0 * wall.half_extents.y,
+                    2.0 * wall.half_extents.z,
+                ))),
+                material: materials.add(map_options.wall_color.into()),
+                transform: Transform::from_translation(wall.center),
+                ..default()
+            })
+            .insert(Collider::cuboid(
+                wall.half_extents.x,
+                wall.half_extents.y,
+                wall.half_extents.z,
+            ))
+            .insert(Wall);
+    }
 
-    // Front
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.wall_width,
-                map_options.wall_height,
-                map_options.wall_thickness,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.0,
-                0.5 * map_options.wall_height,
-                0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.wall_width,
-            0.5 * map_options.wall_height,
-            0.5 * map_options.wall_thickness,
-        ));
-
-    // Back
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.wall_width,
-                map_options.wall_height,
-                map_options.wall_thickness,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.0,
-                0.5 * map_options.wall_height,
-                0.5 * -map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.wall_width,
-            0.5 * map_options.wall_height,
-            0.5 * map_options.wall_thickness,
-        ));
-
-    // Left top
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                1.0 * map_options.wall_thickness,
-                1.0 * map_options.wall_height,
-                0.4 * map_options.wall_length,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * -map_options.wall_width,
-                0.5 * map_options.wall_height,
-                0.3 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.wall_thickness,
-            0.5 * map_options.wall_height,
-            0.2 * map_options.wall_length,
-        ));
-
-    // Left bottom
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                1.0 * map_options.wall_thickness,
-                1.0 * map_options.wall_height,
-                0.4 * map_options.wall_length,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * -map_options.wall_width,
-                0.5 * map_options.wall_height,
-                0.3 * -map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.wall_thickness,
-            0.5 * map_options.wall_height,
-            0.2 * map_options.wall_length,
-        ));
-
-    // Right top
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                1.0 * map_options.wall_thickness,
-                1.0 * map_options.wall_height,
-                0.4 * map_options.wall_length,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * map_options.wall_width,
-                0.5 * map_options.wall_height,
-                0.3 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.wall_thickness,
-            0.5 * map_options.wall_height,
-            0.2 * map_options.wall_length,
-        ));
-
-    // Right bottom
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                1.0 * map_options.wall_thickness,
-                1.0 * map_options.wall_height,
-                0.4 * map_options.wall_length,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * map_options.wall_width,
-                0.5 * map_options.wall_height,
-                0.3 * -map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.wall_thickness,
-            0.5 * map_options.wall_height,
-            0.2 * map_options.wall_length,
-        ));
-
-    // Right goal room
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Plane {
-                size: map_options.goalroom_length,
-            })),
-            material: materials.add(map_options.ground_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * map_options.wall_width + 0.5 * map_options.goalroom_width,
-                0.0,
-                0.5 * map_options.wall_length - 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_length,
-            0.0,
-            0.5 * map_options.goalroom_width,
-        ));
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_length,
-                map_options.goalroom_height,
-                map_options.goalroom_thickness,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * map_options.wall_width + 0.5 * map_options.goalroom_width,
-                0.5 * map_options.goalroom_height,
-                0.5 * -map_options.wall_length
-                    + 0.5 * map_options.goalroom_length
-                    + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_length,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_thickness,
-        ));
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_thickness,
-                map_options.goalroom_height,
-                map_options.goalroom_length,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * map_options.wall_width + map_options.goalroom_width,
-                0.5 * map_options.goalroom_height,
-                0.5 * -map_options.wall_length + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_thickness,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_length,
-        ));
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_length,
-                map_options.goalroom_height,
-                map_options.goalroom_thickness,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * map_options.wall_width + 0.5 * map_options.goalroom_width,
-                0.5 * map_options.goalroom_height,
-                0.5 * -map_options.wall_length - 0.5 * map_options.goalroom_length
-                    + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_length,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_thickness,
-        ));
-
-    // Left goal room - a plane for the ground plus three walls
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Plane {
-                size: map_options.goalroom_length,
-            })),
-            material: materials.add(map_options.ground_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * -map_options.wall_width - 0.5 * map_options.goalroom_width,
+    // Goal room floors, the walls around them are part of `walls`
+    for side in [GateSide::East, GateSide::West] {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Plane {
+                    size: map_options.goalroom_length,
+                })),
+                material: materials.add(map_options.ground_color.into()),
+                transform: Transform::from_translation(map_options.goalroom_center(side)),
+                ..default()
+            })
+            .insert(Collider::cuboid(
+                0.5 * map_options.goalroom_width,
                 0.0,
-                0.5 * -map_options.wall_length + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_length,
-            0.0,
-            0.5 * map_options.goalroom_length,
-        ));
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_length,
-                map_options.goalroom_height,
-                map_options.goalroom_thickness,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * -map_options.wall_width - 0.5 * map_options.goalroom_width,
-                0.5 * map_options.goalroom_height,
-                0.5 * -map_options.wall_length
-                    + 0.5 * map_options.goalroom_length
-                    + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_length,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_thickness,
-        ));
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_thickness,
-                map_options.goalroom_height,
-                map_options.goalroom_length,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * -map_options.wall_width - map_options.goalroom_width,
-                0.5 * map_options.goalroom_height,
-                0.5 * -map_options.wall_length + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_thickness,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_length,
-        ));
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(
-                map_options.goalroom_length,
-                map_options.goalroom_height,
-                map_options.goalroom_thickness,
-            ))),
-            material: materials.add(map_options.wall_color.into()),
-            transform: Transform::from_translation(Vec3::new(
-                0.5 * -map_options.wall_width - 0.5 * map_options.goalroom_width,
-                0.5 * map_options.goalroom_height,
-                0.5 * -map_options.wall_length - 0.5 * map_options.goalroom_length
-                    + 0.5 * map_options.wall_length,
-            )),
-            ..default()
-        })
-        .insert(Collider::cuboid(
-            0.5 * map_options.goalroom_length,
-            0.5 * map_options.goalroom_height,
-            0.5 * map_options.goalroom_thickness,
-        ));
+                0.5 * map_options.goalroom_length,
+            ))
+            .insert(Ground);
+    }
 }
@@ -0,0 +1,507 @@
+use std::collections::{BinaryHeap, VecDeque};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    controller::{MoveCharacters, MoveIntent},
+    gate::GateStateChangedEvent,
+    health::Dead,
+    map::{Ground, MapOptions},
+};
+
+// Side length of one grid cell in world units
+const CELL_SIZE: f32 = 1.0;
+
+// How far obstacles are grown so paths keep a robot's collider clear of walls
+const AGENT_RADIUS: f32 = 1.5;
+
+// Obstacles whose top is lower than this can be stepped over, like a gate sunk into the floor
+const STEP_HEIGHT: f32 = 0.5;
+
+// Distance at which a waypoint counts as reached
+const WAYPOINT_RADIUS: f32 = 0.75;
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(build_nav_grid)
+            .add_system(follow_paths.label(FollowPaths).before(MoveCharacters));
+    }
+}
+
+// Anything that overrides path following for a frame (like direct player input) runs after this
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FollowPaths;
+
+// A walkability grid over the arena floor, on the XZ plane. Rebuilt from the level colliders
+// whenever a gate changes state, so anything holding on to paths can check `is_changed`.
+pub struct NavGrid {
+    min: Vec2,
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+}
+
+// The waypoints a robot is walking through, nearest first. Removed once the last one is reached.
+#[derive(Component)]
+pub struct NavPath {
+    pub waypoints: VecDeque<Vec3>,
+}
+
+#[derive(PartialEq, Eq)]
+struct OpenNode {
+    cost: u32,
+    cell: usize,
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // reversed so the BinaryHeap pops the cheapest node first
+        other.cost.cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        let width = ((max.x - min.x) / CELL_SIZE).ceil() as usize;
+        let height = ((max.y - min.y) / CELL_SIZE).ceil() as usize;
+
+        Self {
+            min,
+            width,
+            height,
+            blocked: vec![false; width * height],
+        }
+    }
+
+    // An empty grid covering the arena and both goal rooms
+    pub fn for_arena(map_options: &MapOptions) -> Self {
+        let half_x = 0.5 * map_options.wall_width + map_options.goalroom_width + CELL_SIZE;
+        let half_z = 0.5 * map_options.wall_length + CELL_SIZE;
+        Self::new(Vec2::new(-half_x, -half_z), Vec2::new(half_x, half_z))
+    }
+
+    // Marks every cell under a box as blocked, grown by `inflate` on each side
+    pub fn block_box(&mut self, center: Vec3, half_extents: Vec3, rotation: Quat, inflate: f32) {
+        // the footprint of a rotated box is covered by its axis aligned bounds
+        let rotation = Mat3::from_quat(rotation);
+        let extents = (rotation.x_axis * half_extents.x).abs()
+            + (rotation.y_axis * half_extents.y).abs()
+            + (rotation.z_axis * half_extents.z).abs();
+
+        if center.y + extents.y < STEP_HEIGHT {
+            return;
+        }
+
+        let min = Vec2::new(
+            center.x - extents.x - inflate,
+            center.z - extents.z - inflate,
+        );
+        let max = Vec2::new(
+            center.x + extents.x + inflate,
+            center.z + extents.z + inflate,
+        );
+
+        let (min_x, min_z) = self.clamped_cell(min);
+        let (max_x, max_z) = self.clamped_cell(max);
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                self.blocked[z * self.width + x] = true;
+            }
+        }
+    }
+
+    pub fn is_walkable(&self, point: Vec3) -> bool {
+        self.cell_of(point)
+            .map_or(false, |cell| !self.blocked[cell])
+    }
+
+    // A* over the grid. Returns the waypoints from start to goal, not including the start, or
+    // None when the goal can't be reached. Both ends are moved to the nearest walkable cell if
+    // they are inside a wall.
+    pub fn find_path(&self, start: Vec3, goal: Vec3) -> Option<Vec<Vec3>> {
+        let start_cell = self.nearest_walkable(self.cell_of(start)?)?;
+        let goal_cell = self.nearest_walkable(self.cell_of(goal)?)?;
+
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut cost_so_far = vec![u32::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+
+        cost_so_far[start_cell] = 0;
+        open.push(OpenNode {
+            cost: self.heuristic(start_cell, goal_cell),
+            cell: start_cell,
+        });
+
+        while let Some(OpenNode { cell, .. }) = open.pop() {
+            if cell == goal_cell {
+                break;
+            }
+
+            for (neighbour, step_cost) in self.neighbours(cell) {
+                let cost = cost_so_far[cell] + step_cost;
+                if cost < cost_so_far[neighbour] {
+                    cost_so_far[neighbour] = cost;
+                    came_from[neighbour] = cell;
+                    open.push(OpenNode {
+                        cost: cost + self.heuristic(neighbour, goal_cell),
+                        cell: neighbour,
+                    });
+                }
+            }
+        }
+
+        if cost_so_far[goal_cell] == u32::MAX {
+            return None;
+        }
+
+        let mut cells = vec![goal_cell];
+        let mut cell = goal_cell;
+        while cell != start_cell {
+            cell = came_from[cell];
+            cells.push(cell);
+        }
+        cells.reverse();
+
+        // skip ahead to the furthest cell that can be walked to in a straight line
+        let mut from = if self.cell_of(start) == Some(start_cell) {
+            Vec3::new(start.x, 0.0, start.z)
+        } else {
+            self.cell_center(start_cell)
+        };
+        let mut waypoints = Vec::new();
+        let mut index = 0;
+        while index + 1 < cells.len() {
+            let mut next = index + 1;
+            while next + 1 < cells.len()
+                && self.is_line_walkable(from, self.cell_center(cells[next + 1]))
+            {
+                next += 1;
+            }
+
+            from = self.cell_center(cells[next]);
+            waypoints.push(from);
+            index = next;
+        }
+
+        // finish exactly where asked, unless that spot had to be moved out of a wall
+        let goal = Vec3::new(goal.x, 0.0, goal.z);
+        match waypoints.last_mut() {
+            Some(last) if self.cell_of(goal) == Some(goal_cell) => *last = goal,
+            None if self.cell_of(goal) == Some(goal_cell) => waypoints.push(goal),
+            _ => {}
+        }
+
+        Some(waypoints)
+    }
+
+    // True when every cell under the straight line between the points is walkable
+    pub fn is_line_walkable(&self, from: Vec3, to: Vec3) -> bool {
+        let offset = Vec3::new(to.x - from.x, 0.0, to.z - from.z);
+        let steps = (offset.length() / (0.25 * CELL_SIZE)).ceil().max(1.0) as usize;
+
+        (0..=steps).all(|step| self.is_walkable(from + offset * (step as f32 / steps as f32)))
+    }
+
+    fn cell_of(&self, point: Vec3) -> Option<usize> {
+        let x = ((point.x - self.min.x) / CELL_SIZE).floor();
+        let z = ((point.z - self.min.y) / CELL_SIZE).floor();
+        if x < 0.0 || z < 0.0 || x as usize >= self.width || z as usize >= self.height {
+            return None;
+        }
+
+        Some(z as usize * self.width + x as usize)
+    }
+
+    fn clamped_cell(&self, point: Vec2) -> (usize, usize) {
+        let x = ((point.x - self.min.x) / CELL_SIZE).floor();
+        let z = ((point.y - self.min.y) / CELL_SIZE).floor();
+        (
+            (x.max(0.0) as usize).min(self.width - 1),
+            (z.max(0.0) as usize).min(self.height - 1),
+        )
+    }
+
+    fn cell_center(&self, cell: usize) -> Vec3 {
+        let x = (cell % self.width) as f32;
+        let z = (cell / self.width) as f32;
+        Vec3::new(
+            self.min.x + (x + 0.5) * CELL_SIZE,
+            0.0,
+            self.min.y + (z + 0.5) * CELL_SIZE,
+        )
+    }
+
+    // Octile distance, scaled the same way as the step costs
+    fn heuristic(&self, from: usize, to: usize) -> u32 {
+        let dx = ((from % self.width) as i64 - (to % self.width) as i64).unsigned_abs() as u32;
+        let dz = ((from / self.width) as i64 - (to / self.width) as i64).unsigned_abs() as u32;
+        10 * dx.max(dz) + 4 * dx.min(dz)
+    }
+
+    // Walkable neighbours with the cost of stepping to them, 10 straight and 14 diagonal
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        let x = (cell % self.width) as i64;
+        let z = (cell / self.width) as i64;
+
+        [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |(dx, dz)| {
+            let walkable = |x: i64, z: i64| {
+                x >= 0
+                    && z >= 0
+                    && (x as usize) < self.width
+                    && (z as usize) < self.height
+                    && !self.blocked[z as usize * self.width + x as usize]
+            };
+
+            if !walkable(x + dx, z + dz) {
+                return None;
+            }
+
+            // no cutting corners past the end of a wall
+            if dx != 0 && dz != 0 && (!walkable(x + dx, z) || !walkable(x, z + dz)) {
+                return None;
+            }
+
+            let cost = if dx != 0 && dz != 0 { 14 } else { 10 };
+            Some(((z + dz) as usize * self.width + (x + dx) as usize, cost))
+        })
+    }
+
+    // The closest walkable cell to `cell`, searching outwards
+    fn nearest_walkable(&self, cell: usize) -> Option<usize> {
+        if !self.blocked[cell] {
+            return Some(cell);
+        }
+
+        let mut visited = vec![false; self.blocked.len()];
+        let mut queue = VecDeque::from([cell]);
+        visited[cell] = true;
+
+        while let Some(current) = queue.pop_front() {
+            if !self.blocked[current] {
+                return Some(current);
+            }
+
+            let x = current % self.width;
+            let z = current / self.width;
+            let mut visit = |next: usize| {
+                if !visited[next] {
+                    visited[next] = true;
+                    queue.push_back(next);
+                }
+            };
+
+            if x > 0 {
+                visit(current - 1);
+            }
+            if x + 1 < self.width {
+                visit(current + 1);
+            }
+            if z > 0 {
+                visit(current - self.width);
+            }
+            if z + 1 < self.height {
+                visit(current + self.width);
+            }
+        }
+
+        None
+    }
+}
+
+// Rasterizes the static level colliders (walls and gates) into a NavGrid once they exist, and
+// again whenever a gate opens or closes
+fn build_nav_grid(
+    mut commands: Commands,
+    nav_grid: Option<Res<NavGrid>>,
+    map_options: Res<MapOptions>,
+    mut gate_events: EventReader<GateStateChangedEvent>,
+    colliders: Query<
+        (&Collider, &Transform),
+        (Without<RigidBody>, Without<Sensor>, Without<Ground>),
+    >,
+) {
+    let gates_changed = gate_events.iter().count() > 0;
+    if (nav_grid.is_some() && !gates_changed) || colliders.is_empty() {
+        return;
+    }
+
+    let mut grid = NavGrid::for_arena(&map_options);
+    for (collider, transform) in colliders.iter() {
+        if let Some(cuboid) = collider.as_cuboid() {
+            grid.block_box(
+                transform.translation,
+                cuboid.half_extents(),
+                transform.rotation,
+                AGENT_RADIUS,
+            );
+        }
+    }
+
+    commands.insert_resource(grid);
+}
+
+// Dying abandons a path, so nobody respawns and walks back to where they were headed
+fn follow_paths(
+    mut commands: Commands,
+    mut walkers: Query<(
+        Entity,
+        &Transform,
+        &mut NavPath,
+        &mut MoveIntent,
+        Option<&Dead>,
+    )>,
+) {
+    for (entity, transform, mut path, mut move_intent, dead) in walkers.iter_mut() {
+        if dead.is_some() {
+            path.waypoints.clear();
+        }
+
+        while let Some(waypoint) = path.waypoints.front() {
+            let mut offset = *waypoint - transform.translation;
+            offset.y = 0.0;
+
+            if offset.length() > WAYPOINT_RADIUS {
+                move_intent.0 = offset.normalize();
+                break;
+            }
+
+            path.waypoints.pop_front();
+        }
+
+        if path.waypoints.is_empty() {
+            move_intent.0 = Vec3::ZERO;
+            commands.entity(entity).remove::<NavPath>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::{gate_bounds, GateSide};
+
+    // The grid build_nav_grid makes for the default arena, with the given gates open
+    fn default_arena(open_gates: &[GateSide]) -> (MapOptions, NavGrid) {
+        let map_options = MapOptions::default();
+        let mut grid = NavGrid::for_arena(&map_options);
+
+        for wall in map_options.walls() {
+            grid.block_box(wall.center, wall.half_extents, Quat::IDENTITY, AGENT_RADIUS);
+        }
+        for side in [GateSide::East, GateSide::West] {
+            let mut gate = gate_bounds(&map_options, side);
+            if open_gates.contains(&side) {
+                // where the gate ends up once it has been lowered
+                gate.center.y -= map_options.wall_height;
+            }
+            grid.block_box(gate.center, gate.half_extents, Quat::IDENTITY, AGENT_RADIUS);
+        }
+
+        (map_options, grid)
+    }
+
+    fn assert_path_clear(grid: &NavGrid, start: Vec3, waypoints: &[Vec3]) {
+        let mut from = start;
+        for &to in waypoints {
+            assert!(
+                grid.is_line_walkable(from, to),
+                "path from {from} to {to} goes through a wall"
+            );
+            from = to;
+        }
+    }
+
+    #[test]
+    fn walls_and_closed_gates_are_blocked() {
+        let (map_options, grid) = default_arena(&[]);
+
+        assert!(grid.is_walkable(Vec3::ZERO));
+        assert!(grid.is_walkable(map_options.spawn_point(crate::robot::Team::East)));
+        assert!(!grid.is_walkable(Vec3::new(0.0, 0.0, 0.5 * map_options.wall_length)));
+        assert!(!grid.is_walkable(Vec3::new(0.5 * map_options.wall_width, 0.0, 0.0)));
+        assert!(!grid.is_walkable(Vec3::new(-0.5 * map_options.wall_width, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn path_across_the_arena_is_straight() {
+        let (_, grid) = default_arena(&[]);
+        let start = Vec3::new(-40.0, 0.0, -30.0);
+        let goal = Vec3::new(40.0, 0.0, 30.0);
+
+        let waypoints = grid.find_path(start, goal).unwrap();
+
+        // nothing in the way, so one waypoint straight to the goal
+        assert_eq!(waypoints, vec![goal]);
+    }
+
+    #[test]
+    fn goal_rooms_are_unreachable_while_gates_are_closed() {
+        let (map_options, grid) = default_arena(&[]);
+
+        for side in [GateSide::East, GateSide::West] {
+            let goal = map_options.goalroom_center(side);
+            assert_eq!(grid.find_path(Vec3::ZERO, goal), None);
+        }
+    }
+
+    #[test]
+    fn open_gate_leads_into_its_goal_room_only() {
+        let (map_options, grid) = default_arena(&[GateSide::East]);
+        let start = Vec3::new(0.0, 0.0, -40.0);
+        let goal = map_options.goalroom_center(GateSide::East);
+
+        let waypoints = grid.find_path(start, goal).unwrap();
+        assert_eq!(waypoints.last(), Some(&goal));
+        assert_path_clear(&grid, start, &waypoints);
+
+        let west = map_options.goalroom_center(GateSide::West);
+        assert_eq!(grid.find_path(start, west), None);
+    }
+
+    #[test]
+    fn path_goes_around_the_goal_room_walls() {
+        let (map_options, grid) = default_arena(&[GateSide::East]);
+        // from one back corner of the goal room, through the gate, to the other
+        let corner = 0.5 * map_options.goalroom_length - 3.0;
+        let room = map_options.goalroom_center(GateSide::East);
+        let start = room + Vec3::new(10.0, 0.0, corner);
+        let goal = Vec3::new(0.0, 0.0, corner);
+
+        let waypoints = grid.find_path(start, goal).unwrap();
+        assert!(waypoints.len() > 1);
+        assert_path_clear(&grid, start, &waypoints);
+    }
+
+    #[test]
+    fn ends_inside_walls_are_moved_out() {
+        let (map_options, grid) = default_arena(&[]);
+        let inside_wall = Vec3::new(0.0, 0.0, 0.5 * map_options.wall_length);
+
+        let waypoints = grid.find_path(Vec3::ZERO, inside_wall).unwrap();
+        let end = *waypoints.last().unwrap();
+        assert!(grid.is_walkable(end));
+        assert!(end.distance(inside_wall) < AGENT_RADIUS + 2.0 * CELL_SIZE);
+
+        assert!(grid.find_path(inside_wall, Vec3::ZERO).is_some());
+    }
+}
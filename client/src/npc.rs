@@ -1,27 +1,219 @@
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::*;
+
+use crate::{
+    behaviour::{Destination, RunBehaviours},
+    combat::AttackCooldowns,
+    controller::MoveIntent,
+    difficulty::{BotDifficulty, BotRng, Difficulty},
+    health::{Dead, Health},
+    lobby::Lobby,
+    map::MapOptions,
+    navigation::{FollowPaths, NavGrid, NavPath},
+    perception::Perception,
+    robot::{Robot, RobotModels, Team},
+    skin::{CosmeticLibrary, RobotLook},
+};
+
+// Close enough to a destination to stop walking, a lock's sensor is wider than this
+pub const ARRIVE_RADIUS: f32 = 1.0;
+
+// Seconds between path queries while walking, so bots notice robots and gates moving around
+const REPATH_INTERVAL: f32 = 2.0;
 
 pub struct NpcPlugin;
 
 impl Plugin for NpcPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup);
+        app.add_system(fill_team_slots).add_system(
+            move_to_objectives
+                .label(MoveToObjectives)
+                .after(RunBehaviours)
+                .before(FollowPaths),
+        );
     }
 }
 
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct MoveToObjectives;
+
 #[derive(Component)]
 pub struct NpcPlayer;
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands
-        .spawn_bundle(SceneBundle {
-            scene: asset_server.load("Robot.glb#Scene0").clone(),
-            transform: Transform::from_xyz(10.0, 1.5, 0.0),
-            ..Default::default()
-        })
-        .insert(RigidBody::Dynamic)
-        .insert(Collider::cuboid(1.5, 1.5, 1.5))
-        .insert(ActiveEvents::COLLISION_EVENTS)
+// What a bot is currently doing, as decided by its behaviour tree
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum NpcObjective {
+    #[default]
+    Idle,
+    MoveTo(Destination),
+    // Standing on, or walking to, this lock until it unlocks
+    CaptureLock(Entity),
+    Attack,
+    Flee,
+    Investigate,
+}
+
+#[derive(Component, Default)]
+pub struct NpcBrain {
+    pub objective: NpcObjective,
+    // Where the bot is walking to, if anywhere. Set by the behaviour tree each time it decides.
+    pub destination: Option<Vec3>,
+    // Seconds until the behaviour tree is ticked again
+    pub time_until_decision: f32,
+    // The destination the current path was planned for
+    planned_for: Option<Vec3>,
+    time_since_path: f32,
+}
+
+fn spawn_npc(
+    commands: &mut Commands,
+    models: &RobotModels,
+    team: Team,
+    position: Vec3,
+    difficulty: Difficulty,
+    look: RobotLook,
+    mut rng: BotRng,
+) {
+    let preset = difficulty.preset();
+    let courage = rng.next_f32();
+
+    models
+        .spawn(
+            commands,
+            &models.default_model,
+            team,
+            Transform::from_translation(position + Vec3::new(0.0, 1.5, 0.0)),
+        )
         .insert(NpcPlayer)
-        .insert(Name::new("NPC"));
+        .insert(NpcBrain::default())
+        .insert(look)
+        .insert(Health::new(100.0))
+        .insert(AttackCooldowns::default())
+        .insert(Perception {
+            reaction_time: preset.reaction_time,
+            ..default()
+        })
+        .insert(BotDifficulty {
+            difficulty,
+            preset,
+            courage,
+        })
+        .insert(rng)
+        .insert(Name::new(format!("NPC {:?} ({:?})", team, difficulty)));
+}
+
+// Tops each team up to its slot count, spread out along the z axis in front of their gate
+fn fill_team_slots(
+    mut commands: Commands,
+    models: Res<RobotModels>,
+    cosmetics: Res<CosmeticLibrary>,
+    map_options: Res<MapOptions>,
+    lobby: Res<Lobby>,
+    robots: Query<&Team, With<Robot>>,
+    bots: Query<&Team, With<NpcPlayer>>,
+    mut bots_spawned: Local<u64>,
+) {
+    for team in [Team::East, Team::West] {
+        let count = robots
+            .iter()
+            .filter(|robot_team| **robot_team == team)
+            .count();
+
+        let mut bot = bots.iter().filter(|bot_team| **bot_team == team).count();
+
+        for slot in count..lobby.per_team {
+            let offset = Vec3::new(0.0, 0.0, 4.0 * (slot as f32 - 0.5 * lobby.per_team as f32));
+            let difficulty = lobby.bot_difficulty(team, bot);
+            println!("Adding a {:?} bot to team {:?}", difficulty, team);
+
+            let rng = BotRng::for_bot(lobby.seed, *bots_spawned);
+            let look = cosmetics.bot_look(*bots_spawned as usize);
+            *bots_spawned += 1;
+            bot += 1;
+
+            spawn_npc(
+                &mut commands,
+                &models,
+                team,
+                map_options.spawn_point(team) + offset,
+                difficulty,
+                look,
+                rng,
+            );
+        }
+    }
+}
+
+// Walks each bot to its destination along a NavPath, re-planning every so often
+fn move_to_objectives(
+    mut commands: Commands,
+    time: Res<Time>,
+    map_options: Res<MapOptions>,
+    nav_grid: Option<Res<NavGrid>>,
+    mut npcs: Query<
+        (
+            Entity,
+            &Team,
+            &Transform,
+            &mut NpcBrain,
+            &mut MoveIntent,
+            Option<&NavPath>,
+            Option<&Dead>,
+        ),
+        With<NpcPlayer>,
+    >,
+) {
+    let nav_grid = match nav_grid {
+        Some(nav_grid) => nav_grid,
+        None => return,
+    };
+
+    for (entity, team, transform, mut brain, mut move_intent, nav_path, dead) in npcs.iter_mut() {
+        brain.time_since_path += time.delta_seconds();
+
+        let destination = match (brain.destination, dead) {
+            (Some(destination), None) => destination,
+            _ => {
+                if nav_path.is_some() {
+                    commands.entity(entity).remove::<NavPath>();
+                }
+                move_intent.0 = Vec3::ZERO;
+                brain.objective = NpcObjective::Idle;
+                brain.planned_for = None;
+                continue;
+            }
+        };
+
+        let mut offset = destination - transform.translation;
+        offset.y = 0.0;
+        if offset.length() < ARRIVE_RADIUS {
+            if nav_path.is_some() {
+                commands.entity(entity).remove::<NavPath>();
+            }
+            move_intent.0 = Vec3::ZERO;
+            continue;
+        }
+
+        let planned = brain.planned_for.map_or(false, |planned_for| {
+            planned_for.distance(destination) < ARRIVE_RADIUS
+        });
+        if planned && !nav_grid.is_changed() && brain.time_since_path < REPATH_INTERVAL {
+            continue;
+        }
+        brain.planned_for = Some(destination);
+        brain.time_since_path = 0.0;
+
+        // our own gate may still be shut when bringing a flag home, wait in front of it
+        let waypoints = nav_grid
+            .find_path(transform.translation, destination)
+            .or_else(|| nav_grid.find_path(transform.translation, map_options.spawn_point(*team)));
+
+        match waypoints {
+            Some(waypoints) => {
+                commands.entity(entity).insert(NavPath {
+                    waypoints: waypoints.into(),
+                });
+            }
+            None => move_intent.0 = Vec3::ZERO,
+        }
+    }
 }
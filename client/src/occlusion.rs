@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{camera::CameraFollow, gate::Gate, map::Wall};
+
+// How see-through a wall gets while it hides the followed robot
+const FADED_ALPHA: f32 = 0.2;
+// Alpha per second walls fade in and out at
+const FADE_SPEED: f32 = 4.0;
+// Heights above the robot's origin that the camera checks it can see, roughly feet and head
+const CHECK_HEIGHTS: [f32; 2] = [-1.0, 1.5];
+
+pub struct OcclusionPlugin;
+
+impl Plugin for OcclusionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(attach_occlusion_fade)
+            .add_system(find_occluders.label(FindOccluders))
+            .add_system(fade_occluders.after(FindOccluders));
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct FindOccluders;
+
+// Put on walls and gates so they can fade out when they're between the camera and the robot it
+// follows
+#[derive(Component)]
+pub struct OcclusionFade {
+    pub occluding: bool,
+    alpha: f32,
+}
+
+fn attach_occlusion_fade(
+    mut commands: Commands,
+    occluders: Query<Entity, (Or<(With<Wall>, With<Gate>)>, Without<OcclusionFade>)>,
+) {
+    for entity in occluders.iter() {
+        commands.entity(entity).insert(OcclusionFade {
+            occluding: false,
+            alpha: 1.0,
+        });
+    }
+}
+
+// Casts rays from the camera to the robot it follows and marks every wall or gate they pass
+// through. Nothing is hidden by the overview or free-fly spectator cameras.
+fn find_occluders(
+    rapier_context: Res<RapierContext>,
+    cameras: Query<(&GlobalTransform, &CameraFollow)>,
+    robots: Query<&GlobalTransform>,
+    mut occluders: Query<&mut OcclusionFade>,
+) {
+    for mut fade in occluders.iter_mut() {
+        fade.occluding = false;
+    }
+
+    let (camera_transform, follow) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let robot_transform = match follow.target.and_then(|target| robots.get(target).ok()) {
+        Some(robot_transform) => robot_transform,
+        None => return,
+    };
+
+    let origin = camera_transform.translation();
+    // robots and lock sensors never hide anything
+    let filter = QueryFilter::new().exclude_dynamic().exclude_sensors();
+
+    for height in CHECK_HEIGHTS {
+        let target = robot_transform.translation() + Vec3::Y * height;
+        let offset = target - origin;
+        let distance = offset.length();
+        if distance <= f32::EPSILON {
+            continue;
+        }
+
+        rapier_context.intersections_with_ray(
+            origin,
+            offset / distance,
+            distance,
+            true,
+            filter,
+            |entity, _| {
+                if let Ok(mut fade) = occluders.get_mut(entity) {
+                    fade.occluding = true;
+                }
+                true
+            },
+        );
+    }
+}
+
+fn fade_occluders(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut occluders: Query<(&mut OcclusionFade, &Handle<StandardMaterial>)>,
+) {
+    for (mut fade, material) in occluders.iter_mut() {
+        let target = if fade.occluding { FADED_ALPHA } else { 1.0 };
+        if (fade.alpha - target).abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let step = FADE_SPEED * time.delta_seconds();
+        fade.alpha = if fade.alpha < target {
+            (fade.alpha + step).min(target)
+        } else {
+            (fade.alpha - step).max(target)
+        };
+
+        let material = match materials.get_mut(material) {
+            Some(material) => material,
+            None => continue,
+        };
+        material.base_color.set_a(fade.alpha);
+        // blending sorts and draws slower, so walls go back to opaque once they're solid again
+        material.alpha_mode = if fade.alpha < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        };
+    }
+}
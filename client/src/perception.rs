@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    health::Dead,
+    npc::NpcPlayer,
+    robot::{Robot, Team},
+};
+
+// Height above a robot's origin that it looks from and is looked at
+const EYE_HEIGHT: f32 = 1.0;
+
+pub struct PerceptionPlugin;
+
+impl Plugin for PerceptionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NoiseEvent>()
+            .add_system(attach_perception)
+            .add_system(see.label(Perceive))
+            // both write the same memories, so they take turns in a fixed order to keep seeded
+            // matches replaying the same way
+            .add_system(hear.label(Perceive).after(see));
+    }
+}
+
+// Systems that read what bots know about the world run after this
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Perceive;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseKind {
+    LockCaptureStarted,
+    GateOpening,
+}
+
+// Something happened loud enough for bots nearby to notice, wherever they are looking
+pub struct NoiseEvent {
+    pub kind: NoiseKind,
+    pub position: Vec3,
+    // How far away it can be heard
+    pub range: f32,
+}
+
+// An enemy a bot has seen, and where it was when it was last in sight
+#[derive(Clone, Copy)]
+pub struct KnownEnemy {
+    pub entity: Entity,
+    pub last_seen_position: Vec3,
+    pub time_since_seen: f32,
+    // How long it has been in sight without a break
+    pub time_in_sight: f32,
+    // Set once it has been in sight for the bot's reaction time. Until then the bot acts as if
+    // it hasn't seen it.
+    pub noticed: bool,
+}
+
+impl KnownEnemy {
+    pub fn visible(&self) -> bool {
+        self.time_since_seen <= 0.0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HeardNoise {
+    pub kind: NoiseKind,
+    pub position: Vec3,
+    pub time_since_heard: f32,
+}
+
+// What a bot knows about the robots around it. Behaviour trees read this instead of the world.
+#[derive(Component)]
+pub struct Perception {
+    pub view_distance: f32,
+    // Full width of the sight cone in radians
+    pub field_of_view: f32,
+    // Enemies this close are noticed even behind the bot, as long as there's no wall between
+    pub awareness_radius: f32,
+    // Seconds before an enemy out of sight or a noise is forgotten
+    pub memory: f32,
+    // Seconds an enemy must stay in sight before it is noticed
+    pub reaction_time: f32,
+    pub known_enemies: Vec<KnownEnemy>,
+    pub heard_noises: Vec<HeardNoise>,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            view_distance: 40.0,
+            field_of_view: std::f32::consts::FRAC_PI_3 * 2.0,
+            awareness_radius: 5.0,
+            memory: 8.0,
+            reaction_time: 0.0,
+            known_enemies: Vec::new(),
+            heard_noises: Vec::new(),
+        }
+    }
+}
+
+impl Perception {
+    // An enemy the bot has noticed, whether or not it is still in sight
+    pub fn known_enemy(&self, entity: Entity) -> Option<&KnownEnemy> {
+        self.noticed_enemies().find(|known| known.entity == entity)
+    }
+
+    pub fn noticed_enemies(&self) -> impl Iterator<Item = &KnownEnemy> {
+        self.known_enemies.iter().filter(|known| known.noticed)
+    }
+
+    // The most recent noise still remembered
+    pub fn latest_noise(&self) -> Option<&HeardNoise> {
+        self.heard_noises
+            .iter()
+            .min_by(|a, b| a.time_since_heard.total_cmp(&b.time_since_heard))
+    }
+}
+
+fn attach_perception(
+    mut commands: Commands,
+    npcs: Query<Entity, (With<NpcPlayer>, Without<Perception>)>,
+) {
+    for entity in npcs.iter() {
+        commands.entity(entity).insert(Perception::default());
+    }
+}
+
+// Updates what each bot can see: enemies in range, inside its sight cone and with no wall
+// in the way
+fn see(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut observers: Query<(Entity, &Team, &Transform, &mut Perception), Without<Dead>>,
+    robots: Query<(Entity, &Team, &Transform), (With<Robot>, Without<Dead>)>,
+) {
+    for (observer, team, transform, mut perception) in observers.iter_mut() {
+        let eye = transform.translation + Vec3::Y * EYE_HEIGHT;
+        let forward = transform.rotation * Vec3::Z;
+
+        for known in perception.known_enemies.iter_mut() {
+            // it was in sight last frame, so that counts towards noticing it
+            if known.visible() {
+                known.time_in_sight += time.delta_seconds();
+            }
+            known.time_since_seen += time.delta_seconds();
+        }
+
+        for (enemy, enemy_team, enemy_transform) in robots.iter() {
+            if enemy_team == team {
+                continue;
+            }
+
+            let target = enemy_transform.translation + Vec3::Y * EYE_HEIGHT;
+            let offset = target - eye;
+            let distance = offset.length();
+            if distance > perception.view_distance {
+                continue;
+            }
+
+            let mut flat = offset;
+            flat.y = 0.0;
+            let in_cone = flat.angle_between(forward) <= 0.5 * perception.field_of_view;
+            if !in_cone && distance > perception.awareness_radius {
+                continue;
+            }
+
+            // only the level blocks sight, robots don't hide each other
+            let filter = QueryFilter::new()
+                .exclude_rigid_body(observer)
+                .exclude_dynamic()
+                .exclude_sensors();
+            let blocked = rapier_context
+                .cast_ray(eye, offset / distance, distance, true, filter)
+                .is_some();
+            if blocked {
+                continue;
+            }
+
+            let reaction_time = perception.reaction_time;
+            match perception
+                .known_enemies
+                .iter_mut()
+                .find(|known| known.entity == enemy)
+            {
+                Some(known) => {
+                    // losing sight of it, even briefly, restarts the reaction
+                    if known.time_since_seen > time.delta_seconds() {
+                        known.time_in_sight = 0.0;
+                    }
+                    known.last_seen_position = enemy_transform.translation;
+                    known.time_since_seen = 0.0;
+                    known.noticed |= known.time_in_sight >= reaction_time;
+                }
+                None => perception.known_enemies.push(KnownEnemy {
+                    entity: enemy,
+                    last_seen_position: enemy_transform.translation,
+                    time_since_seen: 0.0,
+                    time_in_sight: 0.0,
+                    noticed: reaction_time <= 0.0,
+                }),
+            }
+        }
+
+        // forget enemies that have been out of sight too long or are no longer around to see
+        let memory = perception.memory;
+        perception
+            .known_enemies
+            .retain(|known| known.time_since_seen < memory && robots.contains(known.entity));
+    }
+}
+
+fn hear(
+    time: Res<Time>,
+    mut listeners: Query<(&Transform, &mut Perception), Without<Dead>>,
+    mut noise_events: EventReader<NoiseEvent>,
+) {
+    let noises: Vec<&NoiseEvent> = noise_events.iter().collect();
+
+    for (transform, mut perception) in listeners.iter_mut() {
+        for noise in perception.heard_noises.iter_mut() {
+            noise.time_since_heard += time.delta_seconds();
+        }
+        let memory = perception.memory;
+        perception
+            .heard_noises
+            .retain(|noise| noise.time_since_heard < memory);
+
+        for noise in noises.iter() {
+            if noise.position.distance(transform.translation) > noise.range {
+                continue;
+            }
+
+            perception.heard_noises.push(HeardNoise {
+                kind: noise.kind,
+                position: noise.position,
+                time_since_heard: 0.0,
+            });
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::EntityCommands, prelude::*, scene::SceneInstance};
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{controller::CharacterControllerBundle, data::load_ron, gate::GateSide};
+
+pub struct RobotPlugin;
+
+impl Plugin for RobotPlugin {
+    fn build(&self, app: &mut App) {
+        let file: RobotModelFile =
+            load_ron("robots.ron").unwrap_or_else(|err| panic!("bad robot model file {}", err));
+        if !file.models.contains_key(&file.default_model) {
+            panic!("default robot model {} is not defined", file.default_model);
+        }
+
+        let asset_server = app.world.resource::<AssetServer>();
+        let models = file
+            .models
+            .into_iter()
+            .map(|(name, model)| {
+                let handle = asset_server.load(model.scene.as_str());
+                let material_handles = model
+                    .materials
+                    .iter()
+                    .map(|(slot, path)| (slot.clone(), asset_server.load(path.as_str())))
+                    .collect();
+                (
+                    name,
+                    RobotModelDefinition {
+                        handle,
+                        material_handles,
+                        ..model
+                    },
+                )
+            })
+            .collect();
+
+        app.insert_resource(RobotModels {
+            default_model: file.default_model,
+            models,
+        })
+        .add_system(rig_robots);
+    }
+}
+
+// Marker for every robot in the arena, local or not. Systems that care about "something that
+// can walk onto a sensor" should query this instead of LocalPlayer.
+#[derive(Component, Default)]
+pub struct Robot;
+
+#[derive(Component, Inspectable, Default, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Team {
+    #[default]
+    East,
+    West,
+}
+
+impl Team {
+    // Each team owns the goal room on its own side of the arena
+    pub fn home_side(self) -> GateSide {
+        match self {
+            Team::East => GateSide::East,
+            Team::West => GateSide::West,
+        }
+    }
+
+    pub fn enemy(self) -> Team {
+        match self {
+            Team::East => Team::West,
+            Team::West => Team::East,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Team::East => Color::rgb(0.8, 0.1, 0.1),
+            Team::West => Color::rgb(0.1, 0.2, 0.8),
+        }
+    }
+}
+
+// Bones other things can be hung from
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Attachment {
+    LeftHand,
+    RightHand,
+    Head,
+}
+
+#[derive(Deserialize)]
+struct RobotModelDefinition {
+    // Asset path of the model's scene, like "Robot.glb#Scene0"
+    scene: String,
+    #[serde(skip)]
+    handle: Handle<Scene>,
+    // Name of the node every bone hangs from
+    skeleton: String,
+    // Names of the bones to find for each attachment
+    #[serde(default)]
+    attachments: HashMap<Attachment, String>,
+    // Asset paths of the model's materials, by the name palettes know them by
+    #[serde(default)]
+    materials: HashMap<String, String>,
+    #[serde(skip)]
+    material_handles: HashMap<String, Handle<StandardMaterial>>,
+}
+
+#[derive(Deserialize)]
+struct RobotModelFile {
+    default_model: String,
+    models: HashMap<String, RobotModelDefinition>,
+}
+
+pub struct RobotModels {
+    pub default_model: String,
+    models: HashMap<String, RobotModelDefinition>,
+}
+
+impl RobotModels {
+    // The name of one of a model's materials, if it's one palettes can recolour
+    pub fn material_slot(&self, model: &str, material: &Handle<StandardMaterial>) -> Option<&str> {
+        self.models
+            .get(model)?
+            .material_handles
+            .iter()
+            .find(|(_, handle)| *handle == material)
+            .map(|(slot, _)| slot.as_str())
+    }
+
+    // Spawns a robot of the named model, falling back to the default model if there's no such
+    // model. Its RobotRig is added once the model has loaded, which can be frames later.
+    pub fn spawn<'w, 's, 'a>(
+        &self,
+        commands: &'a mut Commands<'w, 's>,
+        model: &str,
+        team: Team,
+        transform: Transform,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let model = if self.models.contains_key(model) {
+            model
+        } else {
+            println!("No robot model {}, using {}", model, self.default_model);
+            self.default_model.as_str()
+        };
+
+        let mut robot = commands.spawn_bundle(SceneBundle {
+            scene: self.models[model].handle.clone(),
+            transform,
+            ..default()
+        });
+        robot
+            .insert_bundle(CharacterControllerBundle::default())
+            .insert(Collider::cuboid(1.5, 1.5, 1.5))
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(Robot)
+            .insert(team)
+            .insert(RobotModel(model.to_string()));
+        robot
+    }
+}
+
+// Which of the RobotModels a robot was spawned as
+#[derive(Component)]
+pub struct RobotModel(pub String);
+
+// The parts of a robot's model other systems need, found once its scene has finished spawning.
+// Anything the model turned out not to have is left out.
+#[derive(Component)]
+pub struct RobotRig {
+    // Plays the robot's animations
+    pub animation_player: Option<Entity>,
+    pub skeleton: Option<Entity>,
+    attachments: HashMap<Attachment, Entity>,
+}
+
+impl RobotRig {
+    pub fn attachment(&self, attachment: Attachment) -> Option<Entity> {
+        self.attachments.get(&attachment).copied()
+    }
+}
+
+fn rig_robots(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    models: Res<RobotModels>,
+    robots: Query<(Entity, &RobotModel, &SceneInstance), Without<RobotRig>>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    animation_players: Query<(), With<AnimationPlayer>>,
+) {
+    for (entity, model, instance) in robots.iter() {
+        // every robot's scene loads in its own time
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+        let definition = &models.models[&model.0];
+
+        let animation_player = find_descendant(entity, &children, &|entity| {
+            animation_players.contains(entity)
+        });
+        let skeleton = find_named(entity, &children, &names, &definition.skeleton);
+        if animation_player.is_none() || skeleton.is_none() {
+            println!(
+                "Robot model {} has no animation player or skeleton",
+                model.0
+            );
+        }
+
+        let mut attachments = HashMap::new();
+        for (attachment, bone) in definition.attachments.iter() {
+            // bones can share a name with the mesh they move, so only look inside the skeleton
+            match skeleton.and_then(|skeleton| find_named(skeleton, &children, &names, bone)) {
+                Some(bone) => {
+                    attachments.insert(*attachment, bone);
+                }
+                None => println!(
+                    "Robot model {} has no {} bone for {:?}",
+                    model.0, bone, attachment
+                ),
+            }
+        }
+
+        commands.entity(entity).insert(RobotRig {
+            animation_player,
+            skeleton,
+            attachments,
+        });
+    }
+}
+
+// The first entity below `entity` that matches, searching parents before their children
+fn find_descendant(
+    entity: Entity,
+    children: &Query<&Children>,
+    matches: &dyn Fn(Entity) -> bool,
+) -> Option<Entity> {
+    for child in children
+        .get(entity)
+        .into_iter()
+        .flat_map(|children| children.iter())
+    {
+        if matches(*child) {
+            return Some(*child);
+        }
+        if let Some(found) = find_descendant(*child, children, matches) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn find_named(
+    entity: Entity,
+    children: &Query<&Children>,
+    names: &Query<&Name>,
+    name: &str,
+) -> Option<Entity> {
+    find_descendant(entity, children, &|entity| {
+        names.get(entity).map_or(false, |n| n.as_str() == name)
+    })
+}
@@ -0,0 +1,444 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{load_config, save_config},
+    input_map::{Action, Actions, Binding, InputButton, InputMap, SuspendReason},
+};
+
+const BUTTON_COLOR: Color = Color::rgb(0.2, 0.2, 0.25);
+const HOVERED_COLOR: Color = Color::rgb(0.3, 0.3, 0.4);
+const WAITING_COLOR: Color = Color::rgb(0.6, 0.4, 0.1);
+
+const CONFIG_FILE: &str = "settings.ron";
+
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .init_resource::<SettingsMenu>()
+            .add_startup_system(setup)
+            .add_system(toggle_menu.label(MenuSystem::Toggle))
+            .add_system(
+                capture_binding
+                    .label(MenuSystem::Capture)
+                    .after(MenuSystem::Toggle),
+            )
+            .add_system(click_buttons.after(MenuSystem::Capture))
+            .add_system(update_labels.after(MenuSystem::Capture))
+            .add_system(toggle_settings)
+            .add_system(update_setting_labels);
+    }
+}
+
+// Escape has to be seen by whichever of these owns it first, and the click that starts a rebind
+// mustn't be captured as the new binding
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+enum MenuSystem {
+    Toggle,
+    Capture,
+}
+
+// Everything in the menu that isn't a binding, kept between runs
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Some players find a shaking camera uncomfortable
+    pub screen_shake: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { screen_shake: true }
+    }
+}
+
+impl Settings {
+    // The saved settings, or the defaults if there aren't any or they can't be read
+    fn load() -> Self {
+        match load_config::<Settings>(CONFIG_FILE) {
+            Ok(settings) => settings.unwrap_or_default(),
+            Err(err) => {
+                println!("Ignoring saved settings, {}", err);
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        if let Err(err) = save_config(CONFIG_FILE, self) {
+            println!("Couldn't save settings, {}", err);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SettingsMenu {
+    pub open: bool,
+    // The action waiting for a new binding, if any
+    rebinding: Option<Rebinding>,
+}
+
+#[derive(Clone, Copy)]
+struct Rebinding {
+    action: Action,
+    // Axes take two presses, this holds the first
+    negative: Option<InputButton>,
+}
+
+#[derive(Component)]
+struct SettingsMenuRoot;
+
+#[derive(Component)]
+struct RebindButton(Action);
+
+// The text on a RebindButton
+#[derive(Component)]
+struct BindingText(Action);
+
+#[derive(Component)]
+struct ResetButton;
+
+#[derive(Component)]
+struct ScreenShakeButton;
+
+// The text on the ScreenShakeButton
+#[derive(Component)]
+struct ScreenShakeText;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/DejaVuSans.ttf");
+    let text_style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    // A dark panel over the whole screen, hidden until escape is pressed
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                display: Display::None,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        })
+        .insert(SettingsMenuRoot)
+        .insert(Name::new("Settings Menu"))
+        .with_children(|parent| {
+            parent.spawn_bundle(
+                TextBundle::from_section("Controls", text_style(40.0)).with_style(Style {
+                    margin: UiRect::all(Val::Px(16.0)),
+                    ..default()
+                }),
+            );
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(600.0), Val::Auto),
+                        justify_content: JustifyContent::SpaceBetween,
+                        align_items: AlignItems::Center,
+                        margin: UiRect {
+                            bottom: Val::Px(16.0),
+                            ..UiRect::all(Val::Px(2.0))
+                        },
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn_bundle(TextBundle::from_section("Screen shake", text_style(20.0)));
+                    row.spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(340.0), Val::Px(32.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(ScreenShakeButton)
+                    .with_children(|button| {
+                        button
+                            .spawn_bundle(TextBundle::from_section("", text_style(16.0)))
+                            .insert(ScreenShakeText);
+                    });
+                });
+
+            for action in Action::ALL {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(600.0), Val::Auto),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::all(Val::Px(2.0)),
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn_bundle(TextBundle::from_section(
+                            action.label(),
+                            text_style(20.0),
+                        ));
+                        row.spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(340.0), Val::Px(32.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            color: BUTTON_COLOR.into(),
+                            ..default()
+                        })
+                        .insert(RebindButton(action))
+                        .with_children(|button| {
+                            button
+                                .spawn_bundle(TextBundle::from_section("", text_style(16.0)))
+                                .insert(BindingText(action));
+                        });
+                    });
+            }
+
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(200.0), Val::Px(36.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: UiRect::all(Val::Px(16.0)),
+                        ..default()
+                    },
+                    color: BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .insert(ResetButton)
+                .with_children(|button| {
+                    button.spawn_bundle(TextBundle::from_section(
+                        "Reset to defaults",
+                        text_style(20.0),
+                    ));
+                });
+
+            parent.spawn_bundle(TextBundle::from_section(
+                "Click a binding to change it, escape to cancel or close",
+                text_style(16.0),
+            ));
+        });
+}
+
+// Escape opens and closes the menu. Gameplay doesn't see any input while it's open.
+fn toggle_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut menu: ResMut<SettingsMenu>,
+    mut actions: ResMut<Actions>,
+    mut roots: Query<&mut Style, With<SettingsMenuRoot>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Escape) || menu.rebinding.is_some() {
+        return;
+    }
+
+    menu.open = !menu.open;
+    actions.set_suspended(SuspendReason::SettingsMenu, menu.open);
+    for mut style in roots.iter_mut() {
+        style.display = if menu.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+// Takes the next key, mouse button or gamepad button pressed as the new binding
+fn capture_binding(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut menu: ResMut<SettingsMenu>,
+    mut input_map: ResMut<InputMap>,
+) {
+    let rebinding = match menu.rebinding {
+        Some(rebinding) => rebinding,
+        None => return,
+    };
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        menu.rebinding = None;
+        return;
+    }
+
+    let pressed = keyboard_input
+        .get_just_pressed()
+        .map(|key| InputButton::Key(*key))
+        .chain(
+            mouse_input
+                .get_just_pressed()
+                .map(|button| InputButton::Mouse(*button)),
+        )
+        .chain(
+            gamepad_buttons
+                .get_just_pressed()
+                .map(|button| InputButton::Gamepad(button.button_type)),
+        )
+        .find(|button| button.is_bindable());
+    let pressed = match pressed {
+        Some(pressed) => pressed,
+        None => return,
+    };
+
+    let binding = match (rebinding.action.axis_directions(), rebinding.negative) {
+        (None, _) => Binding::Button(pressed),
+        (Some(_), None) => {
+            menu.rebinding = Some(Rebinding {
+                negative: Some(pressed),
+                ..rebinding
+            });
+            return;
+        }
+        (Some(_), Some(negative)) => Binding::Pair {
+            negative,
+            positive: pressed,
+        },
+    };
+
+    println!("Bound {:?} to {}", rebinding.action, binding.label());
+    input_map.rebind(rebinding.action, binding);
+    input_map.save();
+    menu.rebinding = None;
+}
+
+fn click_buttons(
+    mut menu: ResMut<SettingsMenu>,
+    mut input_map: ResMut<InputMap>,
+    mut rebind_buttons: Query<
+        (&Interaction, &RebindButton, &mut UiColor),
+        (Changed<Interaction>, Without<ResetButton>),
+    >,
+    mut reset_buttons: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<ResetButton>),
+    >,
+) {
+    if !menu.open {
+        return;
+    }
+
+    for (interaction, button, mut color) in rebind_buttons.iter_mut() {
+        let waiting = menu
+            .rebinding
+            .map_or(false, |rebinding| rebinding.action == button.0);
+        match interaction {
+            Interaction::Clicked => {
+                menu.rebinding = Some(Rebinding {
+                    action: button.0,
+                    negative: None,
+                });
+            }
+            // keep showing which button is waiting for a key
+            _ if waiting => {}
+            Interaction::Hovered => *color = HOVERED_COLOR.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+        }
+    }
+
+    for (interaction, mut color) in reset_buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                *input_map = InputMap::default();
+                input_map.save();
+                menu.rebinding = None;
+            }
+            Interaction::Hovered => *color = HOVERED_COLOR.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+        }
+    }
+}
+
+// Shows each action's bindings, or a prompt on the one waiting for a key
+fn update_labels(
+    menu: Res<SettingsMenu>,
+    input_map: Res<InputMap>,
+    mut texts: Query<(&BindingText, &mut Text)>,
+    mut buttons: Query<(&RebindButton, &mut UiColor)>,
+) {
+    if !menu.is_changed() && !input_map.is_changed() {
+        return;
+    }
+
+    for (binding_text, mut text) in texts.iter_mut() {
+        let action = binding_text.0;
+        let waiting = menu
+            .rebinding
+            .filter(|rebinding| rebinding.action == action);
+
+        text.sections[0].value = match (waiting, action.axis_directions()) {
+            (Some(rebinding), Some((negative, positive))) => match rebinding.negative {
+                None => format!("Press the button for {}", negative),
+                Some(_) => format!("Press the button for {}", positive),
+            },
+            (Some(_), None) => "Press a button".to_string(),
+            (None, _) => input_map
+                .get(action)
+                .iter()
+                .map(|binding| binding.label())
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+    }
+
+    for (button, mut color) in buttons.iter_mut() {
+        let waiting = menu
+            .rebinding
+            .map_or(false, |rebinding| rebinding.action == button.0);
+        *color = if waiting { WAITING_COLOR } else { BUTTON_COLOR }.into();
+    }
+}
+
+fn toggle_settings(
+    menu: Res<SettingsMenu>,
+    mut settings: ResMut<Settings>,
+    mut buttons: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<ScreenShakeButton>),
+    >,
+) {
+    if !menu.open {
+        return;
+    }
+
+    for (interaction, mut color) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                settings.screen_shake = !settings.screen_shake;
+                settings.save();
+            }
+            Interaction::Hovered => *color = HOVERED_COLOR.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+        }
+    }
+}
+
+fn update_setting_labels(
+    settings: Res<Settings>,
+    mut texts: Query<&mut Text, With<ScreenShakeText>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = if settings.screen_shake { "On" } else { "Off" }.to_string();
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::{CameraFollow, MoveCamera},
+    cinematic::PlayCinematic,
+    health::DamageEvent,
+    player::LocalPlayer,
+    settings_menu::Settings,
+};
+
+// Trauma lost per second, a full shake settles in about a second
+const TRAUMA_DECAY: f32 = 1.0;
+// Furthest the camera is thrown and turned at full trauma
+const MAX_OFFSET: f32 = 1.5;
+const MAX_ANGLE: f32 = 0.08;
+// Roughly how many times a second the shake changes direction
+const SHAKE_FREQUENCY: f32 = 15.0;
+// How quickly a kick springs back, higher is snappier
+const IMPULSE_RECOVERY: f32 = 8.0;
+
+// Trauma from being hit, per point of damage, and how hard the camera is knocked with it
+const HIT_TRAUMA: f32 = 0.015;
+const HIT_IMPULSE: f32 = 0.03;
+
+pub struct ScreenShakePlugin;
+
+impl Plugin for ScreenShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenShake>()
+            .add_system(shake_on_hits)
+            .add_system(apply_shake.after(MoveCamera).after(PlayCinematic));
+    }
+}
+
+// Anything can shake the screen by adding trauma here, or knock the camera with an impulse. The
+// shake grows with the square of the trauma, so small knocks stay subtle and big ones stack up.
+#[derive(Default)]
+pub struct ScreenShake {
+    trauma: f32,
+    // An offset the camera is knocked by, springing back to nothing
+    impulse: Vec3,
+    // Where the camera is looking, so far away events shake it less
+    listener: Vec3,
+    time: f32,
+}
+
+impl ScreenShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    // Full trauma at the origin fading to none at `range` from it
+    pub fn add_trauma_at(&mut self, origin: Vec3, amount: f32, range: f32) {
+        let falloff = 1.0 - (origin.distance(self.listener) / range).min(1.0);
+        self.add_trauma(amount * falloff);
+    }
+
+    pub fn add_impulse(&mut self, impulse: Vec3) {
+        self.impulse += impulse;
+    }
+}
+
+// Being hurt shakes the screen in proportion to the damage and knocks the camera away from the
+// hit
+fn shake_on_hits(
+    mut screen_shake: ResMut<ScreenShake>,
+    player: Query<(Entity, &Transform), With<LocalPlayer>>,
+    sources: Query<&Transform>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    let (player, player_transform) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for event in damage_events.iter() {
+        if event.target != player {
+            continue;
+        }
+
+        screen_shake.add_trauma(event.amount * HIT_TRAUMA);
+        let source = event.source.and_then(|source| sources.get(source).ok());
+        if let Some(source_transform) = source {
+            let away =
+                (player_transform.translation - source_transform.translation).normalize_or_zero();
+            screen_shake.add_impulse(away * event.amount * HIT_IMPULSE);
+        }
+    }
+}
+
+// Runs once the camera has been placed for the frame, and the camera places itself from scratch
+// every frame, so the shake never builds up in its transform
+fn apply_shake(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut screen_shake: ResMut<ScreenShake>,
+    mut cameras: Query<(&mut Transform, Option<&CameraFollow>), With<Camera>>,
+) {
+    let dt = time.delta_seconds();
+    screen_shake.time += dt;
+    screen_shake.trauma = (screen_shake.trauma - TRAUMA_DECAY * dt).max(0.0);
+    let recovery = (-IMPULSE_RECOVERY * dt).exp();
+    screen_shake.impulse *= recovery;
+
+    let (mut transform, follow) = match cameras.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    if let Some(follow) = follow {
+        screen_shake.listener = follow.focus;
+    }
+
+    if !settings.screen_shake {
+        return;
+    }
+
+    let shake = screen_shake.trauma * screen_shake.trauma;
+    let t = screen_shake.time * SHAKE_FREQUENCY;
+    let offset = Vec3::new(wobble(t, 0.0), wobble(t, 1.0), wobble(t, 2.0)) * MAX_OFFSET * shake;
+    let rotation = Quat::from_euler(
+        EulerRot::YXZ,
+        wobble(t, 3.0) * MAX_ANGLE * shake,
+        wobble(t, 4.0) * MAX_ANGLE * shake,
+        wobble(t, 5.0) * MAX_ANGLE * shake,
+    );
+
+    transform.translation += offset + screen_shake.impulse;
+    transform.rotation *= rotation;
+}
+
+// Smooth noise from -1 to 1, a different pattern for each seed. Sines at unrelated frequencies
+// never quite line up, so it doesn't visibly repeat.
+fn wobble(t: f32, seed: f32) -> f32 {
+    let a = (t * 1.0 + seed * 12.9898).sin();
+    let b = (t * 2.173 + seed * 78.233).sin();
+    let c = (t * 3.741 + seed * 37.719).sin();
+    (a + 0.5 * b + 0.25 * c) / 1.75
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trauma_falls_off_with_distance_and_caps() {
+        let mut screen_shake = ScreenShake::default();
+        screen_shake.add_trauma_at(Vec3::new(50.0, 0.0, 0.0), 1.0, 100.0);
+        assert!((screen_shake.trauma - 0.5).abs() < 1e-5);
+
+        screen_shake.add_trauma_at(Vec3::new(150.0, 0.0, 0.0), 1.0, 100.0);
+        assert!((screen_shake.trauma - 0.5).abs() < 1e-5);
+
+        screen_shake.add_trauma(2.0);
+        assert_eq!(screen_shake.trauma, 1.0);
+    }
+}
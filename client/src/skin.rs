@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::load_ron,
+    robot::{Attachment, RobotModel, RobotModels, RobotRig, Team},
+};
+
+pub struct SkinPlugin;
+
+impl Plugin for SkinPlugin {
+    fn build(&self, app: &mut App) {
+        let library: CosmeticLibrary =
+            load_ron("cosmetics.ron").unwrap_or_else(|err| panic!("bad cosmetics file {}", err));
+        if !library.palettes.contains_key(&library.default_palette) {
+            panic!("default palette {} is not defined", library.default_palette);
+        }
+
+        app.insert_resource(library).add_system(dress_robots);
+    }
+}
+
+// How a robot is dressed, chosen in the lobby. Only names go in here so it can be serialized,
+// but nothing sends it to other players yet.
+#[derive(Component, Serialize, Deserialize, Clone, Default, Debug)]
+pub struct RobotLook {
+    // None wears the default palette
+    pub palette: Option<String>,
+    pub cosmetics: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+enum CosmeticColor {
+    Team,
+    Rgb(f32, f32, f32),
+}
+
+impl CosmeticColor {
+    fn resolve(self, team: Team) -> Color {
+        match self {
+            CosmeticColor::Team => team.color(),
+            CosmeticColor::Rgb(r, g, b) => Color::rgb(r, g, b),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+enum CosmeticShape {
+    Cuboid { x: f32, y: f32, z: f32 },
+    Sphere { radius: f32 },
+    Capsule { radius: f32, depth: f32 },
+    Torus { radius: f32, ring_radius: f32 },
+}
+
+impl CosmeticShape {
+    fn mesh(self) -> Mesh {
+        match self {
+            CosmeticShape::Cuboid { x, y, z } => shape::Box::new(x, y, z).into(),
+            CosmeticShape::Sphere { radius } => shape::Icosphere {
+                radius,
+                subdivisions: 3,
+            }
+            .into(),
+            CosmeticShape::Capsule { radius, depth } => shape::Capsule {
+                radius,
+                depth,
+                ..default()
+            }
+            .into(),
+            CosmeticShape::Torus {
+                radius,
+                ring_radius,
+            } => shape::Torus {
+                radius,
+                ring_radius,
+                ..default()
+            }
+            .into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CosmeticDefinition {
+    attachment: Attachment,
+    shape: CosmeticShape,
+    color: CosmeticColor,
+    offset: Vec3,
+}
+
+// Kept in name order, so bots are handed out the same looks every match
+#[derive(Deserialize)]
+pub struct CosmeticLibrary {
+    default_palette: String,
+    // Colours by material name
+    palettes: BTreeMap<String, HashMap<String, CosmeticColor>>,
+    cosmetics: BTreeMap<String, CosmeticDefinition>,
+}
+
+impl CosmeticLibrary {
+    // Bots work through the palettes in turn, each wearing at most one cosmetic
+    pub fn bot_look(&self, bot: usize) -> RobotLook {
+        let palette = self.palettes.keys().nth(bot % self.palettes.len()).cloned();
+        let cosmetic = self
+            .cosmetics
+            .keys()
+            .map(Some)
+            .chain([None])
+            .nth(bot % (self.cosmetics.len() + 1))
+            .flatten();
+
+        RobotLook {
+            palette,
+            cosmetics: cosmetic.into_iter().cloned().collect(),
+        }
+    }
+}
+
+// The material a mesh in a robot's model had before it was recoloured
+#[derive(Component)]
+struct OriginalMaterial(Handle<StandardMaterial>);
+
+// The cosmetics spawned for a robot's current look
+#[derive(Component)]
+struct Dressed(Vec<Entity>);
+
+// Recolours a robot and hangs its cosmetics once its model has loaded, and again whenever its
+// look changes
+fn dress_robots(
+    mut commands: Commands,
+    models: Res<RobotModels>,
+    library: Res<CosmeticLibrary>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    robots: Query<
+        (
+            Entity,
+            &RobotModel,
+            &RobotRig,
+            &RobotLook,
+            &Team,
+            Option<&Dressed>,
+        ),
+        Or<(Changed<RobotLook>, Added<RobotRig>)>,
+    >,
+    children: Query<&Children>,
+    mut model_materials: Query<(&mut Handle<StandardMaterial>, Option<&OriginalMaterial>)>,
+) {
+    for (entity, model, rig, look, team, dressed) in robots.iter() {
+        let palette_name = look.palette.as_ref().unwrap_or(&library.default_palette);
+        let palette = match library.palettes.get(palette_name) {
+            Some(palette) => palette,
+            None => {
+                println!(
+                    "No palette {}, using {}",
+                    palette_name, library.default_palette
+                );
+                &library.palettes[&library.default_palette]
+            }
+        };
+
+        // each recoloured material is shared by every mesh of the robot that uses it
+        let mut recoloured: HashMap<&str, Handle<StandardMaterial>> = HashMap::new();
+        let mut meshes_below = Vec::new();
+        collect_descendants(entity, &children, &mut meshes_below);
+        for mesh_entity in meshes_below {
+            let (mut material, original) = match model_materials.get_mut(mesh_entity) {
+                Ok(material) => material,
+                Err(_) => continue,
+            };
+            let original = match original {
+                Some(original) => original.0.clone(),
+                None => {
+                    commands
+                        .entity(mesh_entity)
+                        .insert(OriginalMaterial(material.clone()));
+                    material.clone()
+                }
+            };
+
+            let slot = models.material_slot(&model.0, &original);
+            let color = slot.and_then(|slot| Some((slot, palette.get(slot)?)));
+            *material = match color {
+                Some((slot, color)) => recoloured
+                    .entry(slot)
+                    .or_insert_with(|| {
+                        let mut recolour = materials.get(&original).cloned().unwrap_or_default();
+                        recolour.base_color = color.resolve(*team);
+                        materials.add(recolour)
+                    })
+                    .clone(),
+                None => original,
+            };
+        }
+
+        for cosmetic in dressed.iter().flat_map(|dressed| dressed.0.iter()) {
+            commands.entity(*cosmetic).despawn_recursive();
+        }
+
+        let mut cosmetics = Vec::new();
+        for name in look.cosmetics.iter() {
+            let definition = match library.cosmetics.get(name) {
+                Some(definition) => definition,
+                None => {
+                    println!("No cosmetic {}", name);
+                    continue;
+                }
+            };
+            let bone = match rig.attachment(definition.attachment) {
+                Some(bone) => bone,
+                None => continue,
+            };
+
+            let cosmetic = commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(definition.shape.mesh()),
+                    material: materials.add(definition.color.resolve(*team).into()),
+                    transform: Transform::from_translation(definition.offset),
+                    ..default()
+                })
+                .insert(Name::new(format!("Cosmetic {}", name)))
+                .id();
+            commands.entity(bone).add_child(cosmetic);
+            cosmetics.push(cosmetic);
+        }
+        commands.entity(entity).insert(Dressed(cosmetics));
+    }
+}
+
+// Everything below `entity`
+fn collect_descendants(entity: Entity, children: &Query<&Children>, descendants: &mut Vec<Entity>) {
+    for child in children
+        .get(entity)
+        .into_iter()
+        .flat_map(|children| children.iter())
+    {
+        descendants.push(*child);
+        collect_descendants(*child, children, descendants);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bots_get_every_palette_and_sometimes_no_cosmetic() {
+        let library: CosmeticLibrary =
+            ron::from_str(include_str!("../assets/cosmetics.ron")).expect("bad cosmetics file");
+
+        let looks: Vec<RobotLook> = (0..20).map(|bot| library.bot_look(bot)).collect();
+        for palette in library.palettes.keys() {
+            assert!(looks
+                .iter()
+                .any(|look| look.palette.as_ref() == Some(palette)));
+        }
+        assert!(looks.iter().any(|look| look.cosmetics.is_empty()));
+        assert!(looks.iter().all(|look| look.cosmetics.len() <= 1));
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+
+use crate::{
+    health::{Dead, DeathEvent},
+    input_map::{Action, Actions},
+    player::LocalPlayer,
+    robot::Robot,
+};
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Spectator>()
+            .add_system(update_spectator.label(UpdateSpectator));
+    }
+}
+
+// The camera reads the spectator after this
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UpdateSpectator;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SpectatorView {
+    // Chases one robot the way a player's camera does
+    #[default]
+    Follow,
+    // Frames the whole arena
+    Overview,
+    // Flown around with the movement actions
+    FreeFly,
+}
+
+impl SpectatorView {
+    fn next(self) -> Self {
+        match self {
+            SpectatorView::Follow => SpectatorView::Overview,
+            SpectatorView::Overview => SpectatorView::FreeFly,
+            SpectatorView::FreeFly => SpectatorView::Follow,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Spectator {
+    // Set whenever there is no living local player to follow: they've been killed, they're only
+    // watching, or the match is a replay
+    pub active: bool,
+    pub view: SpectatorView,
+    // The robot the Follow view chases
+    pub target: Option<Entity>,
+}
+
+fn update_spectator(
+    actions: Res<Actions>,
+    mut spectator: ResMut<Spectator>,
+    local_player: Query<(Entity, Option<&Dead>), With<LocalPlayer>>,
+    robots: Query<(Entity, Option<&Dead>), With<Robot>>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    let local_player = local_player.get_single().ok();
+    let was_active = spectator.active;
+    spectator.active = !matches!(local_player, Some((_, None)));
+
+    // a player who has just been killed gets to watch whoever did it
+    for event in death_events.iter() {
+        if local_player.map(|(entity, _)| entity) == Some(event.entity) {
+            spectator.view = SpectatorView::Follow;
+            spectator.target = event.killer.filter(|killer| robots.contains(*killer));
+        }
+    }
+
+    if !spectator.active {
+        return;
+    }
+    if !was_active {
+        println!("Spectating");
+    }
+
+    if actions.just_pressed(Action::SpectateView) {
+        spectator.view = spectator.view.next();
+        println!("Spectator view {:?}", spectator.view);
+    }
+
+    // cycle through the living robots, in the same order every time
+    let mut candidates: Vec<Entity> = robots
+        .iter()
+        .filter(|(_, dead)| dead.is_none())
+        .map(|(entity, _)| entity)
+        .collect();
+    candidates.sort();
+    if candidates.is_empty() {
+        spectator.target = None;
+        return;
+    }
+
+    let step = if actions.just_pressed(Action::SpectateNext) {
+        1
+    } else if actions.just_pressed(Action::SpectatePrevious) {
+        -1
+    } else {
+        0
+    };
+    // whoever was being watched may have died since
+    let current = spectator
+        .target
+        .and_then(|target| candidates.iter().position(|candidate| *candidate == target));
+    let index = match current {
+        Some(current) => (current as isize + step).rem_euclid(candidates.len() as isize) as usize,
+        None => 0,
+    };
+    spectator.target = Some(candidates[index]);
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{health::Dead, robot::Robot};
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatusEffectEvent>()
+            .add_system(attach_status_effects)
+            .add_system(apply_status_effects)
+            .add_system(tick_status_effects)
+            .add_system(clear_on_death);
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StatusEffectKind {
+    // No movement, attacks or abilities
+    Stun,
+    // Movement speed reduced by `magnitude` per stack
+    Slow,
+    // No movement, but attacks and abilities still work
+    Root,
+    // Shoved away from the source with an impulse of `magnitude`, briefly losing control
+    Knockback,
+}
+
+// What happens when an effect is applied to a robot that already has it
+pub enum StackRule {
+    // Keep a single instance and extend it to the longer of the two durations
+    Refresh,
+    // Add a stack, up to the limit, and restart the duration
+    Stack { max_stacks: u32 },
+    // The new application is dropped
+    IgnoreWhileActive,
+}
+
+impl StatusEffectKind {
+    pub const ALL: [StatusEffectKind; 4] = [
+        StatusEffectKind::Stun,
+        StatusEffectKind::Slow,
+        StatusEffectKind::Root,
+        StatusEffectKind::Knockback,
+    ];
+
+    pub fn stack_rule(self) -> StackRule {
+        match self {
+            StatusEffectKind::Stun => StackRule::Refresh,
+            StatusEffectKind::Slow => StackRule::Stack { max_stacks: 3 },
+            StatusEffectKind::Root => StackRule::Refresh,
+            // otherwise a couple of hits can juggle a robot across the arena
+            StatusEffectKind::Knockback => StackRule::IgnoreWhileActive,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            StatusEffectKind::Stun => Color::rgb(1.0, 0.9, 0.1),
+            StatusEffectKind::Slow => Color::rgb(0.2, 0.8, 1.0),
+            StatusEffectKind::Root => Color::rgb(0.5, 0.3, 0.1),
+            StatusEffectKind::Knockback => Color::rgb(1.0, 0.5, 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    pub duration: f32,
+    pub remaining: f32,
+    pub magnitude: f32,
+    pub stacks: u32,
+}
+
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn get(&self, kind: StatusEffectKind) -> Option<&StatusEffect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn has(&self, kind: StatusEffectKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    // Movement and attack input is ignored entirely
+    pub fn blocks_input(&self) -> bool {
+        self.has(StatusEffectKind::Stun) || self.has(StatusEffectKind::Knockback)
+    }
+
+    // Factor to scale a robot's base movement speed by
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.blocks_input() || self.has(StatusEffectKind::Root) {
+            return 0.0;
+        }
+
+        match self.get(StatusEffectKind::Slow) {
+            Some(slow) => (1.0 - slow.magnitude).max(0.0).powi(slow.stacks as i32),
+            None => 1.0,
+        }
+    }
+
+    fn apply(&mut self, kind: StatusEffectKind, duration: f32, magnitude: f32) -> bool {
+        if let Some(existing) = self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            match kind.stack_rule() {
+                StackRule::Refresh => {
+                    // the icon drains over the new duration, rather than sitting full until
+                    // the remaining time falls back under the old one
+                    if duration > existing.remaining {
+                        existing.duration = duration;
+                        existing.remaining = duration;
+                    }
+                    existing.magnitude = existing.magnitude.max(magnitude);
+                }
+                StackRule::Stack { max_stacks } => {
+                    existing.stacks = (existing.stacks + 1).min(max_stacks);
+                    existing.duration = duration;
+                    existing.remaining = duration;
+                }
+                StackRule::IgnoreWhileActive => return false,
+            }
+            return true;
+        }
+
+        self.effects.push(StatusEffect {
+            kind,
+            duration,
+            remaining: duration,
+            magnitude,
+            stacks: 1,
+        });
+        true
+    }
+}
+
+pub struct ApplyStatusEffectEvent {
+    pub target: Entity,
+    pub kind: StatusEffectKind,
+    pub duration: f32,
+    pub magnitude: f32,
+    // Where the effect came from, knockbacks push away from this point
+    pub origin: Option<Vec3>,
+}
+
+fn attach_status_effects(
+    mut commands: Commands,
+    robots: Query<Entity, (With<Robot>, Without<StatusEffects>)>,
+) {
+    for entity in robots.iter() {
+        commands.entity(entity).insert(StatusEffects::default());
+    }
+}
+
+fn apply_status_effects(
+    mut commands: Commands,
+    mut robots: Query<(&mut StatusEffects, &Transform), Without<Dead>>,
+    mut status_events: EventReader<ApplyStatusEffectEvent>,
+) {
+    for event in status_events.iter() {
+        let (mut status_effects, transform) = match robots.get_mut(event.target) {
+            Ok(robot) => robot,
+            Err(_) => continue,
+        };
+
+        if !status_effects.apply(event.kind, event.duration, event.magnitude) {
+            continue;
+        }
+
+        if event.kind == StatusEffectKind::Knockback {
+            let mut direction = match event.origin {
+                Some(origin) => transform.translation - origin,
+                None => transform.rotation * -Vec3::Z,
+            };
+            direction.y = 0.0;
+
+            commands.entity(event.target).insert(ExternalImpulse {
+                impulse: direction.normalize_or_zero() * event.magnitude,
+                ..default()
+            });
+        }
+    }
+}
+
+fn tick_status_effects(time: Res<Time>, mut robots: Query<&mut StatusEffects>) {
+    for mut status_effects in robots.iter_mut() {
+        if status_effects.effects.is_empty() {
+            continue;
+        }
+
+        for effect in status_effects.effects.iter_mut() {
+            effect.remaining -= time.delta_seconds();
+        }
+        status_effects
+            .effects
+            .retain(|effect| effect.remaining > 0.0);
+    }
+}
+
+fn clear_on_death(mut robots: Query<&mut StatusEffects, Added<Dead>>) {
+    for mut status_effects in robots.iter_mut() {
+        status_effects.effects.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refreshing_keeps_the_longer_duration() {
+        let mut status_effects = StatusEffects::default();
+        assert!(status_effects.apply(StatusEffectKind::Stun, 1.0, 0.0));
+        assert!(status_effects.apply(StatusEffectKind::Stun, 5.0, 0.0));
+
+        let stun = status_effects.get(StatusEffectKind::Stun).unwrap();
+        assert_eq!(stun.remaining, 5.0);
+        assert_eq!(stun.duration, 5.0);
+
+        // a shorter stun doesn't cut the longer one short
+        assert!(status_effects.apply(StatusEffectKind::Stun, 2.0, 0.0));
+        let stun = status_effects.get(StatusEffectKind::Stun).unwrap();
+        assert_eq!(stun.remaining, 5.0);
+        assert_eq!(stun.duration, 5.0);
+        assert_eq!(status_effects.effects.len(), 1);
+    }
+
+    #[test]
+    fn stacking_caps_at_max_stacks() {
+        let max_stacks = match StatusEffectKind::Slow.stack_rule() {
+            StackRule::Stack { max_stacks } => max_stacks,
+            _ => panic!("slows should stack"),
+        };
+
+        let mut status_effects = StatusEffects::default();
+        for _ in 0..max_stacks + 2 {
+            assert!(status_effects.apply(StatusEffectKind::Slow, 2.0, 0.2));
+        }
+
+        let slow = status_effects.get(StatusEffectKind::Slow).unwrap();
+        assert_eq!(slow.stacks, max_stacks);
+        assert_eq!(slow.remaining, 2.0);
+    }
+
+    #[test]
+    fn knockbacks_are_ignored_while_active() {
+        let mut status_effects = StatusEffects::default();
+        assert!(status_effects.apply(StatusEffectKind::Knockback, 0.3, 10.0));
+        assert!(!status_effects.apply(StatusEffectKind::Knockback, 1.0, 50.0));
+
+        let knockback = status_effects.get(StatusEffectKind::Knockback).unwrap();
+        assert_eq!(knockback.remaining, 0.3);
+        assert_eq!(knockback.magnitude, 10.0);
+    }
+}